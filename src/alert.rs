@@ -0,0 +1,228 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Above,
+    Below,
+}
+
+/// Condition on the ratio between two market caps, e.g. `ethereum/bitcoin > 0.5`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub numerator: String,
+    pub denominator: String,
+    pub comparison: Comparison,
+    pub threshold: f64,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// Parses `numerator/denominator > threshold` or `<`, the threshold may be a percentage
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rule '{s}', use e.g. 'ethereum/bitcoin > 0.5'");
+        let (pair, comparison, threshold) = match (s.split_once('>'), s.split_once('<')) {
+            (Some((pair, threshold)), None) => (pair, Comparison::Above, threshold),
+            (None, Some((pair, threshold))) => (pair, Comparison::Below, threshold),
+            _ => return Err(invalid()),
+        };
+        let (numerator, denominator) = pair.split_once('/').ok_or_else(invalid)?;
+        let (numerator, denominator) = (numerator.trim(), denominator.trim());
+        if numerator.is_empty() || denominator.is_empty() {
+            return Err(invalid());
+        }
+        let threshold = threshold.trim();
+        let threshold = match threshold.strip_suffix('%') {
+            Some(percentage) => percentage.trim().parse::<f64>().map(|p| p / 100.0),
+            None => threshold.parse::<f64>(),
+        }
+        .map_err(|_| invalid())?;
+        if !threshold.is_finite() || threshold < 0.0 {
+            return Err(invalid());
+        }
+        Ok(Rule {
+            numerator: numerator.to_string(),
+            denominator: denominator.to_string(),
+            comparison,
+            threshold,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let comparison = match self.comparison {
+            Comparison::Above => '>',
+            Comparison::Below => '<',
+        };
+        write!(
+            f,
+            "{}/{} {comparison} {}",
+            self.numerator, self.denominator, self.threshold
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleState {
+    /// Fires as soon as the condition holds
+    #[default]
+    Armed,
+    /// Fired and waits for the ratio to move back past the hysteresis band
+    Fired,
+}
+
+impl Rule {
+    /// Next state of the rule for `ratio` and whether the rule fires. A fired rule is armed
+    /// again once the ratio is back on the other side of the threshold by more than
+    /// `hysteresis` times the threshold, so a ratio hovering around it fires only once.
+    pub fn evaluate(&self, state: RuleState, ratio: f64, hysteresis: f64) -> (RuleState, bool) {
+        let band = self.threshold * hysteresis;
+        let (holds, cleared) = match self.comparison {
+            Comparison::Above => (ratio > self.threshold, ratio < self.threshold - band),
+            Comparison::Below => (ratio < self.threshold, ratio > self.threshold + band),
+        };
+        match state {
+            RuleState::Armed if holds => (RuleState::Fired, true),
+            RuleState::Fired if cleared => (RuleState::Armed, false),
+            state => (state, false),
+        }
+    }
+}
+
+/// States of all rules, keyed by the rule, persisted between runs so that a restart does not
+/// fire rules again
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertState {
+    rules: HashMap<String, RuleState>,
+}
+
+impl AlertState {
+    /// Reads the state file, a missing file results in all rules being armed
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+            .map_err(|e| Error::AlertState(path.to_path_buf(), e))
+    }
+
+    /// Writes the state file, replacing the previous one at once
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let error = |e: std::io::Error| Error::AlertState(path.to_path_buf(), e.to_string());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(error)?;
+        }
+        let temporary = path.with_extension("tmp");
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::AlertState(path.to_path_buf(), e.to_string()))?;
+        fs::write(&temporary, contents).map_err(error)?;
+        fs::rename(&temporary, path).map_err(error)
+    }
+
+    pub fn get(&self, rule: &Rule) -> RuleState {
+        self.rules
+            .get(&rule.to_string())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, rule: &Rule, state: RuleState) {
+        self.rules.insert(rule.to_string(), state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> Rule {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            rule("ethereum/bitcoin > 0.5"),
+            Rule {
+                numerator: String::from("ethereum"),
+                denominator: String::from("bitcoin"),
+                comparison: Comparison::Above,
+                threshold: 0.5,
+            }
+        );
+        let below = rule("AAPL / gold<20%");
+        assert_eq!(below.comparison, Comparison::Below);
+        assert_eq!(below.threshold, 0.2);
+        assert_eq!(below.to_string(), "AAPL/gold < 0.2");
+    }
+
+    #[test]
+    fn test_parse_rule_rejects_invalid_rules() {
+        for invalid in [
+            "ethereum > 0.5",
+            "ethereum/bitcoin 0.5",
+            "ethereum/bitcoin > half",
+            "/bitcoin > 0.5",
+            "ethereum/bitcoin > -1",
+        ] {
+            assert!(invalid.parse::<Rule>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_evaluate_fires_once_with_hysteresis() {
+        let rule = rule("ethereum/bitcoin > 0.5");
+        let mut state = RuleState::Armed;
+        let mut fired = Vec::new();
+        for ratio in [0.48, 0.51, 0.499, 0.502, 0.494, 0.505, 0.51] {
+            let (next, fires) = rule.evaluate(state, ratio, 0.01);
+            state = next;
+            fired.push(fires);
+        }
+        // dipping to 0.499 stays within the 1% band, 0.494 leaves it and re-arms the rule
+        assert_eq!(fired, vec![false, true, false, false, false, true, false]);
+    }
+
+    #[test]
+    fn test_evaluate_below() {
+        let rule = rule("bitcoin/gold < 0.1");
+        assert_eq!(
+            rule.evaluate(RuleState::Armed, 0.09, 0.0),
+            (RuleState::Fired, true)
+        );
+        assert_eq!(
+            rule.evaluate(RuleState::Fired, 0.11, 0.0),
+            (RuleState::Armed, false)
+        );
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let path = std::env::temp_dir()
+            .join(format!("ratio-gang-alert-{}", std::process::id()))
+            .join("alerts.json");
+        let rule = rule("ethereum/bitcoin > 0.5");
+        assert_eq!(
+            AlertState::load(&path).unwrap().get(&rule),
+            RuleState::Armed
+        );
+        let mut state = AlertState::default();
+        state.set(&rule, RuleState::Fired);
+        state.save(&path).unwrap();
+        assert_eq!(
+            AlertState::load(&path).unwrap().get(&rule),
+            RuleState::Fired
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+use crate::provider::{COINGECKO, COINS, COMMODITY, FOREX, POLYGON};
+use crate::transport::fixture_name;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// Default time to live per provider. Commodities and exchange rates use the previous day's close
+/// which only changes once a day, crypto market caps are updated by coingecko about every minute.
+/// The coingecko coin list rarely changes.
+const DEFAULT_TTLS: &[(&str, Duration)] = &[
+    (POLYGON.name, Duration::from_secs(15 * 60)),
+    (COINGECKO.name, Duration::from_secs(60)),
+    (COMMODITY.name, Duration::from_secs(6 * 60 * 60)),
+    (FOREX.name, Duration::from_secs(6 * 60 * 60)),
+    (COINS.name, Duration::from_secs(24 * 60 * 60)),
+];
+const FALLBACK_TTL: Duration = Duration::from_secs(60);
+/// Providers of market data, refreshing skips only their cached responses. Reference data like
+/// the coin list stays cached.
+const MARKET_DATA: &[&str] = &[POLYGON.name, COINGECKO.name, COMMODITY.name, FOREX.name];
+
+/// On-disk cache of successful response bodies, keyed by provider, origin, endpoint and asset.
+/// Failing to read or write the cache never fails a request.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    ttls: HashMap<String, Duration>,
+    refresh: bool,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        let ttls = DEFAULT_TTLS
+            .iter()
+            .map(|(provider, ttl)| (provider.to_string(), *ttl))
+            .collect();
+        Self {
+            dir,
+            ttls,
+            refresh: false,
+        }
+    }
+
+    pub fn with_ttl(mut self, provider: &str, ttl: Duration) -> Self {
+        self.ttls.insert(provider.to_string(), ttl);
+        self
+    }
+
+    /// Ignore cached market data but still store the new responses
+    pub fn with_refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    pub fn ttl(&self, provider: &str) -> Duration {
+        self.ttls.get(provider).copied().unwrap_or(FALLBACK_TTL)
+    }
+
+    /// Responses of different hosts are kept apart, e.g. of a mock server and the real API
+    fn path(&self, provider: &str, url: &Url) -> PathBuf {
+        self.dir
+            .join(provider)
+            .join(origin(url))
+            .join(fixture_name(url))
+    }
+
+    /// Cached body if it is younger than the provider's time to live
+    pub fn get(&self, provider: &str, url: &Url) -> Option<String> {
+        if self.refresh && MARKET_DATA.contains(&provider) {
+            return None;
+        }
+        let path = self.path(provider, url);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age > self.ttl(provider) {
+            return None;
+        }
+        fs::read_to_string(path).ok()
+    }
+
+    pub fn put(&self, provider: &str, url: &Url, body: &str) {
+        let path = self.path(provider, url);
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent).and_then(|_| fs::write(&path, body));
+        }
+    }
+}
+
+/// Scheme, host and port of `url` as a directory name, e.g. `https_api.polygon.io_443`
+fn origin(url: &Url) -> String {
+    let origin = format!(
+        "{}_{}_{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    );
+    origin
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("ratio-gang-{name}-{}", std::process::id()));
+        Cache::new(dir)
+    }
+
+    #[test]
+    fn test_cache_returns_fresh_body() {
+        let cache = temp_cache("cache-fresh");
+        let url = Url::parse("https://api.polygon.io/v3/reference/tickers/AAPL").unwrap();
+        cache.put("polygon", &url, "{}");
+        assert_eq!(cache.get("polygon", &url), Some(String::from("{}")));
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_keeps_hosts_apart() {
+        let cache = temp_cache("cache-hosts");
+        let url = Url::parse("https://api.coingecko.com/api/v3/coins/list").unwrap();
+        let mock = Url::parse("http://localhost:8080/api/v3/coins/list").unwrap();
+        cache.put("coins", &mock, "[]");
+        assert_eq!(cache.get("coins", &url), None);
+        assert_eq!(cache.get("coins", &mock), Some(String::from("[]")));
+        assert_eq!(origin(&mock), "http_localhost_8080");
+        assert_eq!(origin(&url), "https_api.coingecko.com_443");
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_skips_expired_body() {
+        let cache = temp_cache("cache-expired").with_ttl("polygon", Duration::ZERO);
+        let url = Url::parse("https://api.polygon.io/v3/reference/tickers/AAPL").unwrap();
+        cache.put("polygon", &url, "{}");
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(cache.get("polygon", &url), None);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_refresh_skips_body() {
+        let cache = temp_cache("cache-refresh").with_refresh(true);
+        let url = Url::parse("https://api.polygon.io/v3/reference/tickers/AAPL").unwrap();
+        cache.put("polygon", &url, "{}");
+        assert_eq!(cache.get("polygon", &url), None);
+        let url = Url::parse("https://api.coingecko.com/api/v3/coins/list").unwrap();
+        cache.put("coins", &url, "[]");
+        assert_eq!(cache.get("coins", &url), Some(String::from("[]")));
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_ttl_per_provider() {
+        let cache = temp_cache("cache-ttl").with_ttl("coingecko", Duration::from_secs(300));
+        assert_eq!(cache.ttl("coingecko"), Duration::from_secs(300));
+        assert_eq!(cache.ttl("commodity"), Duration::from_secs(6 * 60 * 60));
+        assert_eq!(cache.ttl("unknown"), FALLBACK_TTL);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::provider::{COINGECKO, COINS};
+use crate::{construct_coingecko_v3_markets_query_url, ApiKeys, BaseUrls, Error, Transport};
+use anyhow::Result;
+use futures::future::join_all;
+use serde::Deserialize;
+use std::str::FromStr;
+use url::Url;
+
+/// Entry of coingecko's `/coins/list`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Coin {
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+}
+
+/// The fields of `/coins/markets` needed to rank coins sharing a symbol
+#[derive(Deserialize, Debug)]
+struct CoinRank {
+    id: String,
+    market_cap_rank: Option<u32>,
+}
+
+/// Coingecko id an asset was resolved to, with a note if other coins match it as well
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinId {
+    pub id: String,
+    pub note: Option<String>,
+}
+
+fn construct_coingecko_v3_coins_list_url(base_url: &str) -> Result<Url, Error> {
+    Url::from_str(&format!("{base_url}/api/v3/coins/list")).map_err(Error::InvalidUrl)
+}
+
+/// All coins known to coingecko. The list only changes when coins are listed or delisted, it is
+/// cached as `coins` for a day.
+pub async fn coin_list(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    apikeys: &ApiKeys,
+) -> Result<Vec<Coin>> {
+    let url = construct_coingecko_v3_coins_list_url(&base_urls.coingecko)?;
+    let header = apikeys.coingecko_header()?;
+    let response = transport
+        .get_with_headers(COINS, url, header.as_slice())
+        .await?;
+    if response.status.is_success() {
+        Ok(serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, String::from("coins/list")))?)
+    } else {
+        Err(Error::CoingeckoApi(response.body).into())
+    }
+}
+
+/// Coins whose id, symbol or name equals `query` ignoring case. Ids take precedence over symbols
+/// and symbols over names, so `ETH` matches the symbol of ethereum and not a coin named Eth.
+pub fn matching_coins<'a>(coins: &'a [Coin], query: &str) -> Vec<&'a Coin> {
+    let fields: [fn(&Coin) -> &str; 3] = [|c| &c.id, |c| &c.symbol, |c| &c.name];
+    fields
+        .iter()
+        .map(|field| {
+            coins
+                .iter()
+                .filter(|coin| field(coin).eq_ignore_ascii_case(query))
+                .collect::<Vec<_>>()
+        })
+        .find(|matches| !matches.is_empty())
+        .unwrap_or_default()
+}
+
+/// Market cap rank of each of `ids`, coins without a rank are left out
+async fn market_cap_ranks(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    ids: &[&str],
+    apikeys: &ApiKeys,
+) -> Result<Vec<(String, u32)>> {
+    let url =
+        construct_coingecko_v3_markets_query_url(&base_urls.coingecko, &ids.join(","), "usd")?;
+    let header = apikeys.coingecko_header()?;
+    let response = transport
+        .get_with_headers(COINGECKO, url, header.as_slice())
+        .await?;
+    if response.status.is_success() {
+        let ranks: Vec<CoinRank> = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, ids.join(",")))?;
+        Ok(ranks
+            .into_iter()
+            .filter_map(|rank| Some((rank.id, rank.market_cap_rank?)))
+            .collect())
+    } else {
+        Err(Error::CoingeckoApi(response.body).into())
+    }
+}
+
+/// Resolves an id, symbol or name like `bitcoin`, `BTC` or `Bitcoin` to its coingecko id with
+/// the coin list, see [`matching_coins`] for the precedence. A symbol shared by several coins
+/// resolves to the one with the best market cap rank.
+pub async fn resolve(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    asset: &str,
+    apikeys: &ApiKeys,
+) -> Result<CoinId> {
+    let coins = coin_list(transport, base_urls, apikeys).await?;
+    resolve_in(transport, base_urls, &coins, asset, apikeys).await
+}
+
+/// Like [`resolve`] for many assets, the coin list is only loaded once. Fails if the coin list
+/// can't be loaded, otherwise each asset is resolved on its own.
+pub async fn resolve_all(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    assets: &[String],
+    apikeys: &ApiKeys,
+) -> Result<Vec<Result<CoinId>>> {
+    let coins = coin_list(transport, base_urls, apikeys).await?;
+    Ok(join_all(
+        assets
+            .iter()
+            .map(|asset| resolve_in(transport, base_urls, &coins, asset, apikeys)),
+    )
+    .await)
+}
+
+async fn resolve_in(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    coins: &[Coin],
+    asset: &str,
+    apikeys: &ApiKeys,
+) -> Result<CoinId> {
+    let matches = matching_coins(coins, asset);
+    let ids: Vec<&str> = matches.iter().map(|coin| coin.id.as_str()).collect();
+    match ids.as_slice() {
+        [] => Err(Error::UnknownCoin(asset.to_string()).into()),
+        [id] => Ok(CoinId {
+            id: id.to_string(),
+            note: None,
+        }),
+        _ => {
+            let ranks = market_cap_ranks(transport, base_urls, &ids, apikeys).await?;
+            let (id, note) = pick_ranked(asset, &ids, &ranks);
+            Ok(CoinId {
+                id,
+                note: Some(note),
+            })
+        }
+    }
+}
+
+/// The id with the best market cap rank, or the first id if none is ranked, and a note naming
+/// the other candidates
+fn pick_ranked(asset: &str, ids: &[&str], ranks: &[(String, u32)]) -> (String, String) {
+    let best = ranks
+        .iter()
+        .filter(|(id, _)| ids.contains(&id.as_str()))
+        .min_by_key(|(_, rank)| *rank);
+    let (id, reason) = match best {
+        Some((id, rank)) => (id.as_str(), format!("market cap rank {rank}")),
+        None => (ids[0], String::from("none is ranked")),
+    };
+    let others: Vec<&str> = ids.iter().copied().filter(|other| *other != id).collect();
+    let note = format!(
+        "{asset} matches {} coins, using {id} ({reason}), use crypto:<id> for one of {}",
+        ids.len(),
+        others.join(", ")
+    );
+    (id.to_string(), note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::coin;
+    use crate::transport::Mode;
+
+    fn coins() -> Vec<Coin> {
+        vec![
+            coin("bitcoin", "btc", "Bitcoin"),
+            coin("ethereum", "eth", "Ethereum"),
+            coin("ethereum-wormhole", "eth", "Ethereum (Wormhole)"),
+            coin("eth-token", "eth2", "ETH"),
+        ]
+    }
+
+    #[test]
+    fn test_deserialize_coins_list() {
+        let coins: Vec<Coin> =
+            serde_json::from_str(r#"[{"id":"bitcoin","symbol":"btc","name":"Bitcoin"}]"#).unwrap();
+        assert_eq!(coins, vec![coin("bitcoin", "btc", "Bitcoin")]);
+    }
+
+    #[test]
+    fn test_matching_coins_by_symbol_and_name() {
+        let coins = coins();
+        let ids = |query| {
+            matching_coins(&coins, query)
+                .iter()
+                .map(|coin| coin.id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("BTC"), vec!["bitcoin"]);
+        assert_eq!(ids("Bitcoin"), vec!["bitcoin"]);
+        assert_eq!(ids("ETH"), vec!["ethereum", "ethereum-wormhole"]);
+        assert!(ids("DOGE").is_empty());
+    }
+
+    #[test]
+    fn test_pick_ranked_prefers_best_rank() {
+        let ranks = vec![
+            (String::from("ethereum-wormhole"), 1200),
+            (String::from("ethereum"), 2),
+        ];
+        let (id, note) = pick_ranked("ETH", &["ethereum", "ethereum-wormhole"], &ranks);
+        assert_eq!(id, "ethereum");
+        assert_eq!(
+            note,
+            "ETH matches 2 coins, using ethereum (market cap rank 2), use crypto:<id> for one of ethereum-wormhole"
+        );
+    }
+
+    #[test]
+    fn test_pick_ranked_without_ranks() {
+        let (id, _) = pick_ranked("ETH", &["ethereum", "ethereum-wormhole"], &[]);
+        assert_eq!(id, "ethereum");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_lower_case_symbols() {
+        let dir = std::env::temp_dir().join(format!("ratio-gang-coins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("api_v3_coins_list.json"),
+            r#"[{"id":"bitcoin","symbol":"btc","name":"Bitcoin"},{"id":"btc-token","symbol":"btct","name":"BTC"}]"#,
+        )
+        .unwrap();
+        let transport = Transport::new(reqwest::Client::new(), Mode::Replay(dir.clone()));
+        let (base_urls, apikeys) = (BaseUrls::default(), ApiKeys::default());
+        let resolve = |asset| resolve(&transport, &base_urls, asset, &apikeys);
+        assert_eq!(resolve("bitcoin").await.unwrap().id, "bitcoin");
+        assert_eq!(resolve("btc").await.unwrap().id, "bitcoin");
+        assert!(resolve("doge").await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+use super::polling_ticker;
+use chrono::{Local, Utc};
+use clap::Args;
+use ratio_gang_cli::alert::{AlertState, Rule, RuleState};
+use ratio_gang_cli::config::{parse_duration, state_dir};
+use ratio_gang_cli::{ProviderRegistry, Quote, QuoteOptions, Transport};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use url::Url;
+
+/// Fraction of the threshold, a negative one would re-arm a rule right after it fired
+fn parse_hysteresis(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if fraction.is_finite() && fraction >= 0.0 => Ok(fraction),
+        _ => Err(format!(
+            "invalid hysteresis '{value}', use a fraction of zero or more like 0.01"
+        )),
+    }
+}
+
+#[derive(Args)]
+pub struct AlertArgs {
+    #[arg(
+        required = true,
+        value_name = "RULE",
+        help = "Ratio condition like 'ethereum/bitcoin > 0.5' or 'AAPL/gold < 20%'"
+    )]
+    rules: Vec<Rule>,
+    #[arg(
+        long,
+        default_value = "5m",
+        value_parser = parse_duration,
+        help = "Time between checks, raised if needed to respect the API rate limits"
+    )]
+    interval: Duration,
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        help = "Shell command to run when a rule fires, the alert is passed as json in $RATIO_GANG_ALERT"
+    )]
+    exec: Option<String>,
+    #[arg(long, value_name = "URL", help = "POST the alert as json to URL")]
+    webhook: Option<Url>,
+    #[arg(
+        long,
+        default_value_t = 0.01,
+        value_name = "FRACTION",
+        value_parser = parse_hysteresis,
+        help = "A fired rule fires again only after the ratio moved back past the threshold by this fraction of the threshold"
+    )]
+    hysteresis: f64,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "File keeping which rules fired across restarts [default: $XDG_STATE_HOME/ratio-gang/alerts.json]"
+    )]
+    state: Option<PathBuf>,
+    #[arg(long, help = "Check the rules once and exit, e.g. when run by cron")]
+    once: bool,
+}
+
+pub async fn run(args: &AlertArgs, registry: &ProviderRegistry, transport: &Transport) {
+    let path = match args
+        .state
+        .clone()
+        .or_else(|| state_dir().map(|dir| dir.join("alerts.json")))
+    {
+        Some(path) => path,
+        None => {
+            eprintln!("Could not locate the alert state, set --state or $HOME");
+            process::exit(1)
+        }
+    };
+    let mut state = match AlertState::load(&path) {
+        Ok(state) => state,
+        Err(error) => {
+            eprint!("{error}");
+            process::exit(1)
+        }
+    };
+    let mut assets: Vec<String> = Vec::new();
+    for rule in &args.rules {
+        for asset in [&rule.numerator, &rule.denominator] {
+            if !assets.contains(asset) {
+                assets.push(asset.clone());
+            }
+        }
+    }
+
+    let mut ticker = polling_ticker(
+        "--interval",
+        args.interval,
+        &assets,
+        &QuoteOptions::default(),
+        registry,
+        transport,
+    );
+    loop {
+        ticker.tick().await;
+        match registry
+            .fetch_all(transport, &assets, &QuoteOptions::default())
+            .await
+        {
+            Ok(quotes) => check(args, &mut state, &quotes).await,
+            Err(error) => eprint!("{error}"),
+        }
+        if let Err(error) = state.save(&path) {
+            eprint!("{error}");
+        }
+        if args.once {
+            return;
+        }
+    }
+}
+
+/// Evaluates every rule against the quotes and notifies about the ones that fire
+async fn check(args: &AlertArgs, state: &mut AlertState, quotes: &[Quote]) {
+    let market_cap = |asset: &str| {
+        quotes
+            .iter()
+            .find(|q| q.asset == asset)
+            .map(|q| q.market_cap)
+            .unwrap_or_default()
+    };
+    for rule in &args.rules {
+        let numerator = market_cap(&rule.numerator);
+        let denominator = market_cap(&rule.denominator);
+        if denominator <= 0.0 {
+            continue;
+        }
+        let ratio = numerator / denominator;
+        let previous = state.get(rule);
+        let (next, fires) = rule.evaluate(previous, ratio, args.hysteresis);
+        state.set(rule, next);
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S");
+        if fires {
+            println!("{time} {rule} fired at {ratio}");
+            let payload = json!({
+                "rule": rule.to_string(),
+                "numerator": { "asset": rule.numerator, "market_cap": numerator as u64 },
+                "denominator": { "asset": rule.denominator, "market_cap": denominator as u64 },
+                "ratio": ratio,
+                "threshold": rule.threshold,
+                "time": Utc::now().to_rfc3339(),
+            });
+            notify(args, &payload).await;
+        } else if previous == RuleState::Fired && next == RuleState::Armed {
+            println!("{time} {rule} armed again at {ratio}");
+        }
+    }
+}
+
+async fn notify(args: &AlertArgs, payload: &Value) {
+    if let Some(command) = &args.exec {
+        let status = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("RATIO_GANG_ALERT", payload.to_string())
+            .status()
+            .await;
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Alert command failed with {status}"),
+            Err(error) => eprintln!("Could not run alert command: {error}"),
+        }
+    }
+    if let Some(url) = &args.webhook {
+        let response = reqwest::Client::new()
+            .post(url.clone())
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        // webhook URLs often carry a token, keep it out of the output
+        if let Err(error) = response.map_err(|e| e.without_url()) {
+            eprintln!("Could not send alert to webhook: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hysteresis_rejects_negative_and_non_finite() {
+        assert_eq!(parse_hysteresis("0.05"), Ok(0.05));
+        assert_eq!(parse_hysteresis("0"), Ok(0.0));
+        assert!(parse_hysteresis("-0.5").is_err());
+        assert!(parse_hysteresis("NaN").is_err());
+        assert!(parse_hysteresis("inf").is_err());
+    }
+}
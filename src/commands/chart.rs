@@ -0,0 +1,258 @@
+use crate::format_percentage;
+use chrono::NaiveDate;
+use colored::*;
+use terminal_size::{terminal_size, Width};
+
+const DEFAULT_WIDTH: usize = 80;
+/// Braille characters have 2x4 dots per cell
+const DOTS_X: usize = 2;
+const DOTS_Y: usize = 4;
+
+/// Terminal width in columns, or 80 if stdout is not a terminal
+pub fn terminal_width() -> usize {
+    match terminal_size() {
+        Some((Width(width), _)) => width as usize,
+        None => DEFAULT_WIDTH,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Marker {
+    Min,
+    Max,
+    Current,
+}
+
+/// Line chart of ratios over time drawn with braille characters, `width` includes the y axis
+/// labels. The minimum, maximum and current (last) ratio are highlighted and listed below.
+pub fn line_chart(
+    points: &[(NaiveDate, f64)],
+    width: usize,
+    height: usize,
+    log_scale: bool,
+) -> Vec<String> {
+    let points: Vec<(NaiveDate, f64)> = points
+        .iter()
+        .copied()
+        .filter(|(_, ratio)| ratio.is_finite() && (!log_scale || *ratio > 0.0))
+        .collect();
+    if points.is_empty() || height == 0 {
+        return vec![String::from("no data")];
+    }
+    let scale = |ratio: f64| if log_scale { ratio.ln() } else { ratio };
+    let unscale = |value: f64| if log_scale { value.exp() } else { value };
+
+    let (min_index, max_index, current_index) = extremes(&points);
+    let low = scale(points[min_index].1);
+    let high = scale(points[max_index].1);
+
+    let labels: Vec<String> = (0..height)
+        .map(|row| {
+            if row == 0 {
+                format_percentage(unscale(high))
+            } else if row == height - 1 {
+                format_percentage(unscale(low))
+            } else if row == height / 2 {
+                format_percentage(unscale((low + high) / 2.0))
+            } else {
+                String::new()
+            }
+        })
+        .collect();
+    let label_width = labels.iter().map(|l| l.len()).max().unwrap_or_default();
+    let plot_width = width.saturating_sub(label_width + 2).max(1);
+
+    // dot coordinates with y pointing down
+    let dots_wide = plot_width * DOTS_X;
+    let dots_high = height * DOTS_Y;
+    let dot = |index: usize, ratio: f64| {
+        let x = if points.len() > 1 {
+            index * (dots_wide - 1) / (points.len() - 1)
+        } else {
+            0
+        };
+        let fraction = if high > low {
+            (scale(ratio) - low) / (high - low)
+        } else {
+            0.5
+        };
+        let y = ((1.0 - fraction) * (dots_high - 1) as f64).round() as usize;
+        (x, y)
+    };
+
+    let mut cells = vec![vec![0u8; plot_width]; height];
+    let mut previous: Option<(usize, usize)> = None;
+    for (index, (_, ratio)) in points.iter().enumerate() {
+        let current = dot(index, *ratio);
+        match previous {
+            Some(start) => draw_line(&mut cells, start, current),
+            None => set_dot(&mut cells, current),
+        }
+        previous = Some(current);
+    }
+
+    let mut markers = vec![vec![None; plot_width]; height];
+    for (index, marker) in [
+        (min_index, Marker::Min),
+        (max_index, Marker::Max),
+        (current_index, Marker::Current),
+    ] {
+        let (x, y) = dot(index, points[index].1);
+        markers[y / DOTS_Y][x / DOTS_X] = Some(marker);
+    }
+
+    let mut lines = Vec::with_capacity(height + 3);
+    for (row, label) in labels.iter().enumerate() {
+        let axis = if label.is_empty() { "│" } else { "┤" };
+        let plot: String = cells[row]
+            .iter()
+            .zip(&markers[row])
+            .map(|(bits, marker)| {
+                let cell = braille(*bits).to_string();
+                match marker {
+                    Some(Marker::Min) => cell.red().to_string(),
+                    Some(Marker::Max) => cell.green().to_string(),
+                    Some(Marker::Current) => cell.yellow().bold().to_string(),
+                    None => cell,
+                }
+            })
+            .collect();
+        lines.push(format!("{:>label_width$} {}{}", label, axis, plot));
+    }
+    lines.push(format!("{:>label_width$} └{}", "", "─".repeat(plot_width)));
+    let first = points[0].0.to_string();
+    let last = points[points.len() - 1].0.to_string();
+    let gap = plot_width.saturating_sub(first.len() + last.len()).max(1);
+    lines.push(format!(
+        "{:>label_width$}  {}{}{}",
+        "",
+        first,
+        " ".repeat(gap),
+        last
+    ));
+    lines.push(format!(
+        "{} {} ({})  {} {} ({})  {} {} ({})",
+        "max".green(),
+        format_percentage(points[max_index].1),
+        points[max_index].0,
+        "min".red(),
+        format_percentage(points[min_index].1),
+        points[min_index].0,
+        "current".yellow().bold(),
+        format_percentage(points[current_index].1),
+        points[current_index].0,
+    ));
+    lines
+}
+
+/// Indices of the minimum, maximum and last point
+fn extremes(points: &[(NaiveDate, f64)]) -> (usize, usize, usize) {
+    let mut min_index = 0;
+    let mut max_index = 0;
+    for (index, (_, ratio)) in points.iter().enumerate() {
+        if *ratio < points[min_index].1 {
+            min_index = index;
+        }
+        if *ratio > points[max_index].1 {
+            max_index = index;
+        }
+    }
+    (min_index, max_index, points.len() - 1)
+}
+
+fn set_dot(cells: &mut [Vec<u8>], (x, y): (usize, usize)) {
+    // bit layout of a braille cell: dots 1-3 and 7 in the left column, 4-6 and 8 in the right
+    const BITS: [[u8; DOTS_Y]; DOTS_X] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    cells[y / DOTS_Y][x / DOTS_X] |= BITS[x % DOTS_X][y % DOTS_Y];
+}
+
+/// Bresenham line between two dots
+fn draw_line(cells: &mut [Vec<u8>], (x0, y0): (usize, usize), (x1, y1): (usize, usize)) {
+    let (x0, y0, x1, y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+    loop {
+        set_dot(cells, (x as usize, y as usize));
+        if x == x1 && y == y1 {
+            return;
+        }
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+fn braille(bits: u8) -> char {
+    char::from_u32(0x2800 + bits as u32).unwrap_or(' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(ratios: &[f64]) -> Vec<(NaiveDate, f64)> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        ratios
+            .iter()
+            .enumerate()
+            .map(|(i, ratio)| (start + chrono::Days::new(i as u64), *ratio))
+            .collect()
+    }
+
+    #[test]
+    fn test_line_chart_layout() {
+        colored::control::set_override(false);
+        let lines = line_chart(&points(&[0.1, 0.3, 0.2, 0.25]), 40, 8, false);
+        // plot rows, x axis, date labels and markers
+        assert_eq!(lines.len(), 8 + 3);
+        assert!(lines[0].starts_with("30% ┤"));
+        assert!(lines[7].starts_with("10% ┤"));
+        for line in &lines[..9] {
+            assert_eq!(line.chars().count(), 40);
+        }
+        assert!(lines[9].contains("2024-01-01") && lines[9].ends_with("2024-01-04"));
+        assert_eq!(
+            lines[10],
+            "max 30% (2024-01-02)  min 10% (2024-01-01)  current 25% (2024-01-04)"
+        );
+    }
+
+    #[test]
+    fn test_line_chart_draws_extremes_in_top_and_bottom_row() {
+        colored::control::set_override(false);
+        let lines = line_chart(&points(&[0.5, 0.1]), 20, 4, false);
+        let plot = |row: usize| lines[row].split_once('┤').unwrap().1.to_string();
+        // the line starts top left and ends bottom right
+        assert_ne!(plot(0).chars().next(), Some(braille(0)));
+        assert_ne!(plot(3).chars().last(), Some(braille(0)));
+    }
+
+    #[test]
+    fn test_line_chart_log_scale_skips_non_positive_ratios() {
+        colored::control::set_override(false);
+        let lines = line_chart(&points(&[0.0, 0.01, 1.0]), 40, 5, true);
+        assert!(lines[0].starts_with("100% ┤"));
+        // midpoint of 1% and 100% on a log scale
+        assert!(lines[2].trim_start().starts_with("10% ┤"));
+        assert!(lines[4].trim_start().starts_with("1.0% ┤"));
+    }
+
+    #[test]
+    fn test_line_chart_without_points() {
+        assert_eq!(line_chart(&[], 40, 5, false), vec!["no data"]);
+    }
+
+    #[test]
+    fn test_extremes() {
+        assert_eq!(extremes(&points(&[0.2, 0.1, 0.4, 0.3])), (1, 2, 3));
+    }
+}
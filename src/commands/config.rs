@@ -0,0 +1,57 @@
+use clap::{Args, Subcommand};
+use ratio_gang_cli::config::{config_path, Config};
+use std::process;
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the settings in effect, combining the config file, environmental variables and
+    /// defaults. API keys are redacted.
+    Show,
+    /// Write a commented config file with all settings
+    Init {
+        #[arg(long, help = "Replace an existing config file")]
+        force: bool,
+    },
+}
+
+/// Runs without loading the config first, `init` never reads an existing file
+pub fn run(args: &ConfigArgs) {
+    match args.command {
+        ConfigCommand::Show => show(),
+        ConfigCommand::Init { force } => match Config::init(force) {
+            Ok(path) => println!("Wrote {}", path.display()),
+            Err(error) => {
+                eprint!("{error}");
+                process::exit(1)
+            }
+        },
+    }
+}
+
+fn show() {
+    let effective = match Config::load().and_then(|config| config.effective()) {
+        Ok(effective) => effective,
+        Err(error) => {
+            eprint!("{error}");
+            process::exit(1)
+        }
+    };
+    match config_path() {
+        Some(path) if path.exists() => println!("# {}", path.display()),
+        Some(path) => println!("# {} does not exist, showing defaults", path.display()),
+        None => println!("# no config file, showing defaults"),
+    }
+    match toml::to_string(&effective) {
+        Ok(toml) => print!("{toml}"),
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(1)
+        }
+    }
+}
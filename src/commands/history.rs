@@ -0,0 +1,150 @@
+use super::chart::{line_chart, terminal_width};
+use crate::{format_percentage, market_cap_formatter, TABLE_BAR_LENGTH};
+use chrono::{Days, NaiveDate, Utc};
+use clap::{Args, ValueEnum};
+use colored::*;
+use ratio_gang_cli::history::{grid, ratio_history, Interval, RatioPoint};
+use ratio_gang_cli::{ProviderRegistry, Transport};
+use serde_json::json;
+use std::process;
+
+/// Extra days fetched before `--from`, so that weekends and holidays at the start of the range
+/// can be filled with the previous trading day
+const LOOKBACK_DAYS: u64 = 7;
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    #[arg(help = "Asset whose market cap is divided by the denominator asset")]
+    numerator: String,
+    #[arg(help = "Asset whose market cap the numerator asset is divided by")]
+    denominator: String,
+    #[arg(long, value_name = "YYYY-MM-DD", help = "First date of the range")]
+    from: NaiveDate,
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        help = "Last date of the range [default: today]"
+    )]
+    to: Option<NaiveDate>,
+    #[arg(
+        long,
+        default_value = "day",
+        value_name = "day|week|month",
+        help = "Spacing between the points of the history"
+    )]
+    interval: Interval,
+    #[arg(long, value_enum, default_value_t, help = "Output format")]
+    format: HistoryFormat,
+    #[arg(long, help = "Use a logarithmic y axis for the chart")]
+    log_scale: bool,
+    #[arg(
+        long,
+        default_value_t = 15,
+        value_name = "ROWS",
+        help = "Height of the chart"
+    )]
+    height: usize,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum HistoryFormat {
+    /// Line chart sized to the terminal width
+    #[default]
+    Chart,
+    /// One row per date with a bar
+    Table,
+    Csv,
+    /// One json object per line
+    Jsonl,
+}
+
+pub async fn run(args: &HistoryArgs, registry: &ProviderRegistry, transport: &Transport) {
+    let today = Utc::now().date_naive();
+    let to = args.to.unwrap_or(today).min(today);
+    if args.from > to {
+        eprintln!("--from must be before --to and not in the future");
+        process::exit(1)
+    }
+    let fetch_from = args.from - Days::new(LOOKBACK_DAYS);
+    let series = tokio::try_join!(
+        registry.history(transport, &args.numerator, fetch_from, to),
+        registry.history(transport, &args.denominator, fetch_from, to),
+    );
+    let (numerator, denominator) = match series {
+        Ok(series) => series,
+        Err(error) => {
+            eprint!("{error}");
+            process::exit(1)
+        }
+    };
+    let points = ratio_history(
+        &numerator,
+        &denominator,
+        &grid(args.from, to, args.interval),
+    );
+    match args.format {
+        HistoryFormat::Chart => print_chart(args, &points),
+        HistoryFormat::Table => print_table(args, &points),
+        HistoryFormat::Csv => print_csv(args, &points),
+        HistoryFormat::Jsonl => print_jsonl(args, &points),
+    }
+}
+
+fn print_chart(args: &HistoryArgs, points: &[RatioPoint]) {
+    let ratios: Vec<(NaiveDate, f64)> = points.iter().map(|p| (p.date, p.ratio)).collect();
+    println!("{} / {}", args.numerator, args.denominator);
+    for line in line_chart(&ratios, terminal_width(), args.height, args.log_scale) {
+        println!("{line}");
+    }
+}
+
+fn print_table(args: &HistoryArgs, points: &[RatioPoint]) {
+    let mut f = market_cap_formatter();
+    let max = points.iter().map(|p| p.ratio).fold(0.0, f64::max);
+    println!("{} / {}", args.numerator, args.denominator);
+    for point in points {
+        let filled_length = if max > 0.0 {
+            (point.ratio / max * TABLE_BAR_LENGTH as f64).round() as usize
+        } else {
+            0
+        };
+        let numerator = f.fmt2(point.numerator).to_string();
+        println!(
+            "{}  {}{}  {:>6}  {} / {}",
+            point.date,
+            "█".repeat(filled_length).green(),
+            " ".repeat(TABLE_BAR_LENGTH - filled_length),
+            format_percentage(point.ratio),
+            numerator,
+            f.fmt2(point.denominator),
+        );
+    }
+}
+
+fn print_csv(args: &HistoryArgs, points: &[RatioPoint]) {
+    println!("date,{},{},ratio", args.numerator, args.denominator);
+    for point in points {
+        println!(
+            "{},{},{},{}",
+            point.date, point.numerator as u64, point.denominator as u64, point.ratio
+        );
+    }
+}
+
+fn print_jsonl(args: &HistoryArgs, points: &[RatioPoint]) {
+    for point in points {
+        let json = json!({
+            "date": point.date,
+            "ratio": point.ratio,
+            "numerator": {
+                "asset": args.numerator,
+                "market_cap": point.numerator as u64
+            },
+            "denominator": {
+                "asset": args.denominator,
+                "market_cap": point.denominator as u64
+            },
+        });
+        println!("{}", json);
+    }
+}
@@ -0,0 +1,79 @@
+use ratio_gang_cli::ratelimit::QueueStatus;
+use ratio_gang_cli::transport::Mode;
+use ratio_gang_cli::{ProviderRegistry, QuoteOptions, Transport};
+use std::time::Duration;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+pub mod alert;
+pub mod chart;
+pub mod config;
+pub mod history;
+pub mod search;
+pub mod watch;
+
+/// Ticks every `interval` for repeated lookups of `assets` in the currency of `options`, the
+/// interval set with `flag` is raised if needed to stay within the rate limits of the APIs. The
+/// first tick is immediate.
+pub fn polling_ticker(
+    flag: &str,
+    interval: Duration,
+    assets: &[String],
+    options: &QuoteOptions,
+    registry: &ProviderRegistry,
+    transport: &Transport,
+) -> Interval {
+    let min_interval = match transport.mode() {
+        // replayed responses don't count against any quota
+        Mode::Replay(_) => Duration::ZERO,
+        _ => registry.min_refresh_interval(assets, options),
+    };
+    if interval < min_interval {
+        eprintln!(
+            "{flag} raised to {}s to stay within the API rate limits",
+            min_interval.as_secs()
+        );
+    }
+    let mut ticker = tokio::time::interval(interval.max(min_interval).max(Duration::from_secs(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
+
+/// Shows on stderr how many requests wait for the API rate limits, the line is cleared once
+/// none waits
+pub fn show_queue(status: &QueueStatus) {
+    match status.ready_at {
+        Some(ready_at) => eprint!(
+            "\r\x1b[K{}",
+            queue_line(
+                status.waiting,
+                ready_at.saturating_duration_since(Instant::now()),
+            )
+        ),
+        None => eprint!("\r\x1b[K"),
+    }
+}
+
+fn queue_line(waiting: usize, left: Duration) -> String {
+    let requests = if waiting == 1 { "request" } else { "requests" };
+    format!(
+        "Waiting for API rate limits: {waiting} {requests} queued, about {}s left",
+        left.as_secs_f64().ceil()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_line() {
+        assert_eq!(
+            queue_line(3, Duration::from_millis(23500)),
+            "Waiting for API rate limits: 3 requests queued, about 24s left"
+        );
+        assert_eq!(
+            queue_line(1, Duration::from_secs(12)),
+            "Waiting for API rate limits: 1 request queued, about 12s left"
+        );
+    }
+}
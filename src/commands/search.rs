@@ -0,0 +1,104 @@
+use clap::Args;
+use ratio_gang_cli::search::{search, SearchMatch};
+use ratio_gang_cli::{ApiKeys, BaseUrls, Transport};
+use std::process;
+
+#[derive(Args)]
+pub struct SearchArgs {
+    #[arg(help = "Part of a name, symbol or coingecko-id, e.g. 'apple' or 'btc'")]
+    query: String,
+    #[arg(
+        long,
+        default_value_t = 10,
+        value_name = "N",
+        help = "Maximum number of stocks and of cryptocurrencies to show"
+    )]
+    limit: usize,
+    #[arg(short, long, help = "Return json")]
+    json: bool,
+}
+
+pub async fn run(
+    args: &SearchArgs,
+    base_urls: &BaseUrls,
+    apikeys: &ApiKeys,
+    transport: &Transport,
+) {
+    let results = search(transport, base_urls, &args.query, args.limit, apikeys).await;
+    for note in &results.notes {
+        eprintln!("note: {note}");
+    }
+    let matches = results.matches;
+    if args.json {
+        match serde_json::to_string(&matches) {
+            Ok(json) => println!("{json}"),
+            Err(error) => {
+                eprintln!("{error}");
+                process::exit(1)
+            }
+        }
+    } else if matches.is_empty() {
+        eprintln!("No asset matches '{}'", args.query);
+        process::exit(1)
+    } else {
+        for line in table(&matches) {
+            println!("{line}");
+        }
+    }
+}
+
+/// One aligned row per match: asset class, identifier, name and exchange
+fn table(matches: &[SearchMatch]) -> Vec<String> {
+    let rows: Vec<[String; 4]> = matches
+        .iter()
+        .map(|m| {
+            [
+                m.market_cap_type.to_string(),
+                m.identifier.clone(),
+                m.name.clone(),
+                m.exchange.clone().unwrap_or_else(|| String::from("-")),
+            ]
+        })
+        .collect();
+    let width = |column: usize| {
+        rows.iter()
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let (class, identifier, name) = (width(0), width(1), width(2));
+    rows.iter()
+        .map(|[c, i, n, e]| format!("{c:<class$}  {i:<identifier$}  {n:<name$}  {e}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratio_gang_cli::MarketCapType;
+
+    #[test]
+    fn test_table_aligns_columns() {
+        let matches = vec![
+            SearchMatch {
+                market_cap_type: MarketCapType::Stock,
+                identifier: String::from("AAPL"),
+                name: String::from("Apple Inc."),
+                exchange: Some(String::from("XNAS")),
+            },
+            SearchMatch {
+                market_cap_type: MarketCapType::Crypto,
+                identifier: String::from("apple-token"),
+                name: String::from("Apple (APPLE)"),
+                exchange: None,
+            },
+        ];
+        assert_eq!(
+            table(&matches),
+            vec![
+                "stock   AAPL         Apple Inc.     XNAS",
+                "crypto  apple-token  Apple (APPLE)  -",
+            ]
+        );
+    }
+}
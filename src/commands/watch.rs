@@ -0,0 +1,241 @@
+use super::polling_ticker;
+use crate::{
+    create_ratio_gauge, market_cap_formatter, market_cap_ratio, ratio_gauge, BAR_LENGTH,
+    TABLE_BAR_LENGTH,
+};
+use chrono::{DateTime, Local};
+use colored::*;
+use ratio_gang_cli::compare::rank;
+use ratio_gang_cli::{ProviderRegistry, Quote, QuoteOptions, Transport};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Fetches the assets every `interval` and redraws the gauge or ranking in place, with the
+/// change of every value since the previous update, in the currency of `options`. Failed
+/// updates keep the last view.
+pub async fn run(
+    assets: &[String],
+    interval: Duration,
+    options: &QuoteOptions,
+    registry: &ProviderRegistry,
+    transport: &Transport,
+) {
+    let mut ticker = polling_ticker("--watch", interval, assets, options, registry, transport);
+
+    let mut previous: Option<Vec<Quote>> = None;
+    let mut body: Vec<String> = Vec::new();
+    let mut updated: Option<DateTime<Local>> = None;
+    let mut drawn = 0;
+    loop {
+        ticker.tick().await;
+        let status = match registry.fetch_all(transport, assets, options).await {
+            Ok(quotes) => {
+                body = render(&quotes, previous.as_deref());
+                previous = Some(quotes);
+                let now = Local::now();
+                updated = Some(now);
+                format!("updated {}", now.format("%H:%M:%S"))
+                    .dimmed()
+                    .to_string()
+            }
+            Err(error) => {
+                let last = match updated {
+                    Some(time) => format!(", last update {}", time.format("%H:%M:%S")),
+                    None => String::new(),
+                };
+                format!("update failed: {}{last}", one_line(&error.to_string()))
+                    .red()
+                    .to_string()
+            }
+        };
+        let mut stdout = io::stdout().lock();
+        if drawn > 0 {
+            // move to the start of the previous view and clear it
+            let _ = write!(stdout, "\x1b[{drawn}F\x1b[J");
+        }
+        for line in body.iter().chain([&status]) {
+            let _ = writeln!(stdout, "{line}");
+        }
+        let _ = stdout.flush();
+        drawn = body.len() + 1;
+    }
+}
+
+/// `message` with its lines joined, so that the status takes the one line it is counted as
+fn one_line(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Gauge for two assets, ranking for more, with changes since `previous`
+fn render(quotes: &[Quote], previous: Option<&[Quote]>) -> Vec<String> {
+    render_lines(quotes, previous)
+        .into_iter()
+        .map(|line| line.trim_end().to_string())
+        .collect()
+}
+
+fn render_lines(quotes: &[Quote], previous: Option<&[Quote]>) -> Vec<String> {
+    let mut f = market_cap_formatter();
+    let previous_cap = |asset: &str| {
+        previous
+            .and_then(|quotes| quotes.iter().find(|q| q.asset == asset))
+            .map(|q| q.market_cap)
+    };
+    if let [a, b] = quotes {
+        let (numerator, denominator) = if a.market_cap < b.market_cap {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let ratio = market_cap_ratio(numerator.market_cap, denominator.market_cap);
+        let previous_ratio = previous_cap(&numerator.asset)
+            .zip(previous_cap(&denominator.asset))
+            .and_then(|(n, d)| market_cap_ratio(n, d));
+        let mut lines = vec![format!(
+            "{}  {}",
+            ratio_gauge(ratio, BAR_LENGTH),
+            ratio_delta(ratio, previous_ratio)
+        )];
+        for quote in [numerator, denominator] {
+            let market_cap = f.fmt2(quote.market_cap).to_string();
+            lines.push(format!(
+                "{}: {}  {}",
+                quote.asset,
+                market_cap,
+                market_cap_delta(quote.market_cap, previous_cap(&quote.asset))
+            ));
+        }
+        lines
+    } else {
+        let ranking = rank(quotes);
+        let width = ranking
+            .iter()
+            .map(|r| r.quote.asset.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max("asset".len());
+        let mut lines = vec![format!(
+            "{:>2}  {:<width$}  {:>10}  {:<TABLE_BAR_LENGTH$}     change",
+            "#", "asset", "market cap", "of largest"
+        )];
+        for r in &ranking {
+            lines.push(format!(
+                "{:>2}  {:<width$}  {:>10}  {}  {}",
+                r.rank,
+                r.quote.asset,
+                f.fmt2(r.quote.market_cap),
+                create_ratio_gauge(r.share_of_largest, TABLE_BAR_LENGTH),
+                market_cap_delta(r.quote.market_cap, previous_cap(&r.quote.asset))
+            ));
+        }
+        lines
+    }
+}
+
+/// Change of a ratio in percentage points
+fn ratio_delta(ratio: Option<f64>, previous: Option<f64>) -> ColoredString {
+    match ratio.zip(previous) {
+        Some((ratio, previous)) => signed(ratio - previous, |d| format!("{:.2} pp", d * 100.0)),
+        None => "".normal(),
+    }
+}
+
+/// Absolute and relative change of a market cap
+fn market_cap_delta(market_cap: f64, previous: Option<f64>) -> ColoredString {
+    match previous.filter(|p| *p > 0.0) {
+        Some(previous) => {
+            let mut f = market_cap_formatter();
+            signed(market_cap - previous, |d| {
+                format!("{} ({:.2}%)", f.fmt2(d), d / previous * 100.0)
+            })
+        }
+        None => "".normal(),
+    }
+}
+
+/// Formats the magnitude of `delta` with its sign, green when rising and red when falling
+fn signed(delta: f64, mut format: impl FnMut(f64) -> String) -> ColoredString {
+    let text = format(delta.abs());
+    if delta > 0.0 {
+        format!("+{text}").green()
+    } else if delta < 0.0 {
+        format!("-{text}").red()
+    } else {
+        format!("±{text}").dimmed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::quote;
+
+    #[test]
+    fn test_one_line_joins_error_lines() {
+        assert_eq!(
+            one_line("Coingecko API did not return expected payload.\nReceived [], expected x\n"),
+            "Coingecko API did not return expected payload. Received [], expected x"
+        );
+    }
+
+    #[test]
+    fn test_render_pair_without_previous_tick() {
+        colored::control::set_override(false);
+        let lines = render(&[quote("ethereum", 250e9), quote("bitcoin", 1e12)], None);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("25%"));
+        assert_eq!(lines[1], "ethereum: 250.0 B");
+        assert_eq!(lines[2], "bitcoin: 1.0 T");
+    }
+
+    #[test]
+    fn test_render_pair_with_deltas() {
+        colored::control::set_override(false);
+        let previous = [quote("ethereum", 200e9), quote("bitcoin", 1e12)];
+        let lines = render(
+            &[quote("ethereum", 250e9), quote("bitcoin", 1e12)],
+            Some(&previous),
+        );
+        assert!(lines[0].ends_with("25%  +5.00 pp"));
+        assert_eq!(lines[1], "ethereum: 250.0 B  +50.0 B (25.00%)");
+        assert_eq!(lines[2], "bitcoin: 1.0 T  ±0 (0.00%)");
+    }
+
+    #[test]
+    fn test_render_pair_without_market_caps() {
+        colored::control::set_override(false);
+        let previous = [quote("ethereum", 200e9), quote("bitcoin", 1e12)];
+        let lines = render(
+            &[quote("ethereum", 0.0), quote("bitcoin", 0.0)],
+            Some(&previous),
+        );
+        assert_eq!(lines[0], "n/a");
+    }
+
+    #[test]
+    fn test_render_ranking_matches_previous_by_asset() {
+        colored::control::set_override(false);
+        let previous = [quote("gold", 15e12), quote("bitcoin", 1e12)];
+        let lines = render(
+            &[
+                quote("bitcoin", 0.9e12),
+                quote("gold", 15e12),
+                quote("ethereum", 0.3e12),
+            ],
+            Some(&previous),
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].contains("gold") && lines[1].ends_with("±0 (0.00%)"));
+        assert!(lines[2].contains("bitcoin") && lines[2].ends_with("-100.0 B (10.00%)"));
+        // no previous market cap, no change
+        assert_eq!(
+            lines[3],
+            " 3  ethereum     300.0 B  [                    ] 2%"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::units::Mass;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Precious metals valued with their polygon.io forex quote in USD per ounce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Commodity {
+    Gold,
+    Silver,
+    Platinum,
+    Palladium,
+}
+
+impl Commodity {
+    pub const ALL: [Commodity; 4] = [
+        Commodity::Gold,
+        Commodity::Silver,
+        Commodity::Platinum,
+        Commodity::Palladium,
+    ];
+
+    /// Recognizes the lowercase and the capitalized name, all caps is left to stock symbols
+    /// since e.g. GOLD is a ticker
+    pub fn from_name(name: &str) -> Option<Commodity> {
+        match name {
+            "gold" | "Gold" => Some(Commodity::Gold),
+            "silver" | "Silver" => Some(Commodity::Silver),
+            "platinum" | "Platinum" => Some(Commodity::Platinum),
+            "palladium" | "Palladium" => Some(Commodity::Palladium),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commodity::Gold => "gold",
+            Commodity::Silver => "silver",
+            Commodity::Platinum => "platinum",
+            Commodity::Palladium => "palladium",
+        }
+    }
+
+    /// Forex pair of the commodity against USD
+    pub fn ticker(&self) -> &'static str {
+        match self {
+            Commodity::Gold => "XAUUSD",
+            Commodity::Silver => "XAGUSD",
+            Commodity::Platinum => "XPTUSD",
+            Commodity::Palladium => "XPDUSD",
+        }
+    }
+
+    /// Rough estimate of the above ground stock. Gold is the World Gold Council
+    /// figure, silver the Silver Institute's estimate of all silver ever mined, platinum and
+    /// palladium are cumulative mine supply estimates.
+    pub fn default_above_ground(&self) -> Mass {
+        match self {
+            Commodity::Gold => Mass::tonnes(212582.0),
+            Commodity::Silver => Mass::tonnes(1751000.0),
+            Commodity::Platinum => Mass::tonnes(10000.0),
+            Commodity::Palladium => Mass::tonnes(8000.0),
+        }
+    }
+}
+
+/// Above ground stock per commodity, commodities without an estimate use their default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AboveGround {
+    stocks: HashMap<Commodity, Mass>,
+}
+
+impl AboveGround {
+    pub fn with(mut self, commodity: Commodity, stock: Mass) -> Self {
+        self.stocks.insert(commodity, stock);
+        self
+    }
+
+    pub fn get(&self, commodity: Commodity) -> Mass {
+        self.stocks
+            .get(&commodity)
+            .copied()
+            .unwrap_or_else(|| commodity.default_above_ground())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Commodity::from_name("silver"), Some(Commodity::Silver));
+        assert_eq!(
+            Commodity::from_name("Palladium"),
+            Some(Commodity::Palladium)
+        );
+        assert_eq!(Commodity::from_name("GOLD"), None);
+        for commodity in Commodity::ALL {
+            assert_eq!(Commodity::from_name(commodity.name()), Some(commodity));
+        }
+    }
+
+    #[test]
+    fn test_above_ground_overrides_default() {
+        let above_ground = AboveGround::default().with(Commodity::Gold, Mass::troy_ounces(6.8e9));
+        assert_eq!(above_ground.get(Commodity::Gold), Mass::troy_ounces(6.8e9));
+        assert_eq!(above_ground.get(Commodity::Silver), Mass::tonnes(1751000.0));
+    }
+}
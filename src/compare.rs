@@ -0,0 +1,104 @@
+use crate::Quote;
+use serde::Serialize;
+
+/// Position of a quote among the compared assets, ordered by market cap
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ranked {
+    pub rank: usize,
+    pub quote: Quote,
+    /// Market cap divided by the largest market cap of the comparison
+    pub share_of_largest: f64,
+    /// Market cap divided by the combined market cap of the comparison
+    pub share_of_total: f64,
+}
+
+/// Ranks quotes by market cap, largest first
+pub fn rank(quotes: &[Quote]) -> Vec<Ranked> {
+    let mut quotes = quotes.to_vec();
+    quotes.sort_by(|a, b| b.market_cap.total_cmp(&a.market_cap));
+    let largest = quotes.first().map(|q| q.market_cap).unwrap_or_default();
+    let total: f64 = quotes.iter().map(|q| q.market_cap).sum();
+    quotes
+        .into_iter()
+        .enumerate()
+        .map(|(index, quote)| Ranked {
+            rank: index + 1,
+            share_of_largest: share(quote.market_cap, largest),
+            share_of_total: share(quote.market_cap, total),
+            quote,
+        })
+        .collect()
+}
+
+/// Pairwise ratios in the order of `quotes`, `matrix[i][j]` is the market cap of
+/// `quotes[i]` divided by the market cap of `quotes[j]`, unset if the latter is 0
+pub fn ratio_matrix(quotes: &[Quote]) -> Vec<Vec<Option<f64>>> {
+    quotes
+        .iter()
+        .map(|row| {
+            quotes
+                .iter()
+                .map(|column| ratio(row.market_cap, column.market_cap))
+                .collect()
+        })
+        .collect()
+}
+
+fn ratio(value: f64, of: f64) -> Option<f64> {
+    (of > 0.0).then(|| value / of)
+}
+
+fn share(value: f64, of: f64) -> f64 {
+    if of > 0.0 {
+        value / of
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::quote;
+
+    #[test]
+    fn test_rank_orders_by_market_cap() {
+        let ranked = rank(&[
+            quote("ethereum", 300.0),
+            quote("gold", 1500.0),
+            quote("bitcoin", 1200.0),
+        ]);
+        let assets: Vec<&str> = ranked.iter().map(|r| r.quote.asset.as_str()).collect();
+        assert_eq!(assets, vec!["gold", "bitcoin", "ethereum"]);
+        assert_eq!(ranked[0].rank, 1);
+        assert_eq!(ranked[2].rank, 3);
+    }
+
+    #[test]
+    fn test_rank_shares() {
+        let ranked = rank(&[quote("bitcoin", 750.0), quote("ethereum", 250.0)]);
+        assert_eq!(ranked[0].share_of_largest, 1.0);
+        assert_eq!(ranked[1].share_of_largest, 250.0 / 750.0);
+        assert_eq!(ranked[0].share_of_total, 0.75);
+        assert_eq!(ranked[1].share_of_total, 0.25);
+    }
+
+    #[test]
+    fn test_rank_without_market_cap() {
+        let ranked = rank(&[quote("foo", 0.0), quote("bar", 0.0)]);
+        assert_eq!(ranked[0].share_of_largest, 0.0);
+        assert_eq!(ranked[0].share_of_total, 0.0);
+    }
+
+    #[test]
+    fn test_ratio_matrix() {
+        let matrix = ratio_matrix(&[
+            quote("bitcoin", 1200.0),
+            quote("ethereum", 300.0),
+            quote("gold", 0.0),
+        ]);
+        assert_eq!(matrix[0], vec![Some(1.0), Some(4.0), None]);
+        assert_eq!(matrix[1], vec![Some(0.25), Some(1.0), None]);
+        assert_eq!(matrix[2], vec![Some(0.0), Some(0.0), None]);
+    }
+}
@@ -0,0 +1,602 @@
+use crate::cache::Cache;
+use crate::provider::{COINGECKO_API, POLYGON_RATE_LIMIT, SOURCES};
+use crate::retry::{RetryPolicies, RetryPolicy};
+use crate::{AboveGround, ApiKeys, BaseUrls, CoingeckoPlan, Commodity, Error, Mass, RateLimit};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Contents of `$XDG_CONFIG_HOME/ratio-gang/config.toml`, see [`TEMPLATE`] for all keys
+///
+/// ```toml
+/// assets = ["bitcoin", "gold"]
+///
+/// [polygon]
+/// base_url = "http://localhost:8080"
+///
+/// [cache.ttl]
+/// commodity = "12h"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Assets compared when none are given
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<String>,
+    /// Output of the comparison when neither `--plain` nor `--json` is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+    pub polygon: ProviderConfig,
+    pub coingecko: CoingeckoConfig,
+    pub cache: CacheConfig,
+    /// Retry policy overrides per provider name
+    pub retry: BTreeMap<String, RetryConfig>,
+    /// Above ground stock per commodity, e.g. `gold = "6.8e9oz"`
+    pub above_ground: BTreeMap<String, String>,
+    /// Names that stand for an asset, e.g. `btc = "bitcoin"`
+    pub aliases: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Human,
+    Plain,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Quota of the plan, 5 for the free plan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoingeckoConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Demo if an API key is set, public otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<CoingeckoPlan>,
+    /// Quota of the plan, defaults to the plan's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+}
+
+/// Debug output of the provider sections shows whether a key is set, never the key
+fn redacted(api_key: &Option<String>) -> Option<&'static str> {
+    api_key.as_ref().map(|_| REDACTED)
+}
+
+impl fmt::Debug for ProviderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProviderConfig")
+            .field("api_key", &redacted(&self.api_key))
+            .field("base_url", &self.base_url)
+            .field("requests_per_minute", &self.requests_per_minute)
+            .finish()
+    }
+}
+
+impl fmt::Debug for CoingeckoConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoingeckoConfig")
+            .field("api_key", &redacted(&self.api_key))
+            .field("base_url", &self.base_url)
+            .field("plan", &self.plan)
+            .field("requests_per_minute", &self.requests_per_minute)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Overrides `$XDG_CACHE_HOME/ratio-gang`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// Time to live per provider name, e.g. `coingecko = "5m"`
+    pub ttl: BTreeMap<String, String>,
+}
+
+/// Overrides of a provider's retry policy, unset fields keep the provider's default
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Zero disables retrying
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry, doubled for every further retry, e.g. `"2s"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_delay: Option<String>,
+    /// Longest delay before a retry, a longer `Retry-After` fails the request at once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay: Option<String>,
+}
+
+/// Commented config file written by `config init`
+pub const TEMPLATE: &str = r#"# ratio-gang configuration, command line flags and environmental variables take precedence
+
+# assets compared when none are given
+# assets = ["ethereum", "bitcoin"]
+
+# output of the comparison: human, plain or json
+# output = "human"
+
+[polygon]
+# api_key = "..."                      # or POLYGON_KEY
+# base_url = "https://api.polygon.io"  # or POLYGON_BASE_URL
+# requests_per_minute = 5              # quota of the plan, requests beyond it are queued
+
+[coingecko]
+# api_key = "..."                         # or COINGECKO_KEY
+# plan = "demo"                           # public, demo or pro, demo if api_key is set
+# base_url = "https://api.coingecko.com"  # or COINGECKO_BASE_URL, pro-api.coingecko.com for pro
+# requests_per_minute = 30                # defaults to 10 public, 30 demo, 500 pro
+
+[cache]
+# dir = "/tmp/ratio-gang"
+
+[cache.ttl]
+# polygon = "15m"
+# coingecko = "1m"
+# commodity = "6h"
+# forex = "6h"
+# coins = "1d"
+
+# retries of rate limited and failed requests per provider name
+# [retry.polygon]
+# max_retries = 3
+# base_delay = "15s"
+# max_delay = "1m"
+
+[above_ground]
+# gold = "212582t"
+# silver = "1751000t"
+# platinum = "10000t"
+# palladium = "8000t"
+
+[aliases]
+# btc = "bitcoin"
+# eth = "ethereum"
+# apple = "AAPL"
+# barrick = "stock:GOLD"
+"#;
+
+/// Assets compared when neither the command line nor the config file name any
+const DEFAULT_ASSETS: [&str; 2] = ["ethereum", "bitcoin"];
+/// Replaces API keys in `config show`
+const REDACTED: &str = "********";
+
+/// `$XDG_<var>_HOME/ratio-gang`, falling back to `$HOME/<fallback>/ratio-gang`
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let home = match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(fallback),
+    };
+    Some(home.join("ratio-gang"))
+}
+
+/// Location of the config file, `$XDG_CONFIG_HOME` falls back to `$HOME/.config`
+pub fn config_path() -> Option<PathBuf> {
+    Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("config.toml"))
+}
+
+/// Location of the response cache, `$XDG_CACHE_HOME` falls back to `$HOME/.cache`
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// Location of persisted state like fired alerts, `$XDG_STATE_HOME` falls back to
+/// `$HOME/.local/state`
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Error for a value of the config file that can't be used
+fn invalid(message: String) -> Error {
+    Error::Config(config_path().unwrap_or_default(), message)
+}
+
+/// Rejects a key of `section` that doesn't name a provider, e.g. a misspelled one
+fn provider_key(section: &str, key: &str) -> Result<(), Error> {
+    if SOURCES.iter().any(|source| source.name == key) {
+        return Ok(());
+    }
+    let names: Vec<&str> = SOURCES.iter().map(|source| source.name).collect();
+    Err(invalid(format!(
+        "unknown provider '{key}' in {section}, use {}",
+        names.join(", ")
+    )))
+}
+
+/// Parses durations like `90`, `90s`, `15m`, `6h` or `1d`, plain numbers are seconds
+pub fn parse_duration(value: &str) -> Result<Duration, Error> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let multiplier = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(Error::InvalidDuration(value.to_string())),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .map(Duration::from_secs)
+        .ok_or_else(|| Error::InvalidDuration(value.to_string()))
+}
+
+impl Config {
+    /// Reads the config file, a missing file results in the default config
+    pub fn load() -> Result<Config, Error> {
+        let path = match config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        };
+        let contents =
+            fs::read_to_string(&path).map_err(|e| Error::Config(path.clone(), e.to_string()))?;
+        toml::from_str(&contents).map_err(|e| Error::Config(path, e.message().to_string()))
+    }
+
+    /// Writes the commented template to the config file, an existing file is only replaced
+    /// with `force`
+    pub fn init(force: bool) -> Result<PathBuf, Error> {
+        let path = config_path()
+            .ok_or_else(|| Error::Config(PathBuf::new(), String::from("$HOME is not set")))?;
+        if path.exists() && !force {
+            return Err(Error::Config(
+                path,
+                String::from("already exists, use --force to replace it"),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::Config(path.clone(), e.to_string()))?;
+        }
+        fs::write(&path, TEMPLATE).map_err(|e| Error::Config(path.clone(), e.to_string()))?;
+        Ok(path)
+    }
+
+    /// API keys with precedence: environmental variables, config file. Missing keys are reported
+    /// by the providers that need them.
+    pub fn api_keys(&self) -> ApiKeys {
+        let key = |var: &str, config: &Option<String>| env::var(var).ok().or(config.clone());
+        let apikeys = ApiKeys::new(
+            key("POLYGON_KEY", &self.polygon.api_key),
+            key("COINGECKO_KEY", &self.coingecko.api_key),
+        );
+        match self.coingecko.plan {
+            Some(plan) => apikeys.with_coingecko_plan(plan),
+            None => apikeys,
+        }
+    }
+
+    /// Configured assets, or ethereum and bitcoin
+    pub fn assets(&self) -> Vec<String> {
+        if self.assets.is_empty() {
+            DEFAULT_ASSETS.iter().map(|a| a.to_string()).collect()
+        } else {
+            self.assets.clone()
+        }
+    }
+
+    pub fn output(&self) -> OutputFormat {
+        self.output.unwrap_or(OutputFormat::Human)
+    }
+
+    /// Above ground stocks with the configured overrides of the commodity defaults
+    pub fn above_ground(&self) -> Result<AboveGround, Error> {
+        let mut above_ground = AboveGround::default();
+        for (name, mass) in &self.above_ground {
+            let commodity = Commodity::from_name(name)
+                .ok_or_else(|| invalid(format!("unknown commodity '{name}' in above_ground")))?;
+            let mass: Mass = mass.parse().map_err(invalid)?;
+            above_ground = above_ground.with(commodity, mass);
+        }
+        Ok(above_ground)
+    }
+
+    /// The settings in effect after applying environmental variables and defaults, with API
+    /// keys redacted
+    pub fn effective(&self) -> Result<Config, Error> {
+        let base_urls = self.base_urls()?;
+        let redacted = |var: &str, config: &Option<String>| {
+            env::var(var)
+                .ok()
+                .or(config.clone())
+                .map(|_| String::from(REDACTED))
+        };
+        let above_ground = self.above_ground()?;
+        let [polygon_limit, coingecko_limit] = self.rate_limits();
+        Ok(Config {
+            assets: self.assets(),
+            output: Some(self.output()),
+            polygon: ProviderConfig {
+                api_key: redacted("POLYGON_KEY", &self.polygon.api_key),
+                base_url: Some(base_urls.polygon().to_string()),
+                requests_per_minute: Some(polygon_limit.requests_per_minute),
+            },
+            coingecko: CoingeckoConfig {
+                api_key: redacted("COINGECKO_KEY", &self.coingecko.api_key),
+                base_url: Some(base_urls.coingecko().to_string()),
+                plan: Some(self.api_keys().coingecko_plan()),
+                requests_per_minute: Some(coingecko_limit.requests_per_minute),
+            },
+            cache: CacheConfig {
+                dir: self.cache.dir.clone().or_else(cache_dir),
+                ttl: self.cache.ttl.clone(),
+            },
+            retry: self.retry.clone(),
+            above_ground: Commodity::ALL
+                .iter()
+                .map(|c| (c.name().to_string(), above_ground.get(*c).to_string()))
+                .collect(),
+            aliases: self.aliases.clone(),
+        })
+    }
+
+    /// Base URLs with precedence: environmental variables, config file, built-in defaults. The
+    /// default coingecko host depends on the plan.
+    pub fn base_urls(&self) -> Result<BaseUrls, Error> {
+        let defaults = BaseUrls::default();
+        let polygon = env::var("POLYGON_BASE_URL")
+            .ok()
+            .or(self.polygon.base_url.clone())
+            .unwrap_or(defaults.polygon);
+        let coingecko = env::var("COINGECKO_BASE_URL")
+            .ok()
+            .or(self.coingecko.base_url.clone())
+            .unwrap_or_else(|| self.api_keys().coingecko_plan().base_url().to_string());
+        BaseUrls::new(&polygon, &coingecko)
+    }
+
+    /// Quotas of polygon.io and coingecko.com, the configured ones or those of the plans
+    pub fn rate_limits(&self) -> [RateLimit; 2] {
+        let coingecko_plan = self.api_keys().coingecko_plan();
+        [
+            RateLimit {
+                requests_per_minute: self
+                    .polygon
+                    .requests_per_minute
+                    .unwrap_or(POLYGON_RATE_LIMIT.requests_per_minute),
+                ..POLYGON_RATE_LIMIT
+            },
+            RateLimit {
+                api: COINGECKO_API,
+                requests_per_minute: self
+                    .coingecko
+                    .requests_per_minute
+                    .unwrap_or(coingecko_plan.requests_per_minute()),
+            },
+        ]
+    }
+
+    /// Response cache with the configured directory and time to live overrides
+    pub fn cache(&self) -> Result<Option<Cache>, Error> {
+        let dir = match self.cache.dir.clone().or_else(cache_dir) {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let mut cache = Cache::new(dir);
+        for (provider, ttl) in &self.cache.ttl {
+            provider_key("cache.ttl", provider)?;
+            cache = cache.with_ttl(provider, parse_duration(ttl)?);
+        }
+        Ok(Some(cache))
+    }
+
+    /// Default retry policies with the configured overrides
+    pub fn retry(&self) -> Result<RetryPolicies, Error> {
+        let mut policies = RetryPolicies::default();
+        for (provider, config) in &self.retry {
+            provider_key("retry", provider)?;
+            let default = policies.get(provider);
+            let duration = |value: &Option<String>, default: Duration| match value {
+                Some(value) => parse_duration(value),
+                None => Ok(default),
+            };
+            let policy = RetryPolicy::new(
+                config.max_retries.unwrap_or(default.max_retries),
+                duration(&config.base_delay, default.base_delay)?,
+                duration(&config.max_delay, default.max_delay)?,
+            );
+            if policy.base_delay > policy.max_delay {
+                return Err(invalid(format!(
+                    "base_delay of retry.{provider} exceeds its max_delay"
+                )));
+            }
+            policies = policies.with_policy(provider, policy);
+        }
+        Ok(policies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_base_urls() {
+        let config: Config = toml::from_str(
+            r#"
+            [polygon]
+            base_url = "http://localhost:8080"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.polygon.base_url,
+            Some(String::from("http://localhost:8080"))
+        );
+        assert_eq!(config.coingecko, CoingeckoConfig::default());
+    }
+
+    #[test]
+    fn test_parse_config_rejects_unknown_fields() {
+        let result = toml::from_str::<Config>("[polygon]\nbase_ulr = \"http://localhost\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_config_cache_ttl() {
+        let config: Config = toml::from_str("[cache.ttl]\ncommodity = \"12h\"").unwrap();
+        assert_eq!(
+            config.cache.ttl.get("commodity"),
+            Some(&String::from("12h"))
+        );
+    }
+
+    #[test]
+    fn test_parse_config_defaults_and_aliases() {
+        let config: Config = toml::from_str(
+            r#"
+            assets = ["btc", "gold"]
+            output = "json"
+
+            [polygon]
+            api_key = "foo"
+
+            [above_ground]
+            gold = "6.8e9oz"
+
+            [aliases]
+            btc = "bitcoin"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.assets(), vec!["btc", "gold"]);
+        assert_eq!(config.output(), OutputFormat::Json);
+        assert_eq!(config.polygon.api_key, Some(String::from("foo")));
+        assert_eq!(config.aliases.get("btc"), Some(&String::from("bitcoin")));
+        let above_ground = config.above_ground().unwrap();
+        assert_eq!(above_ground.get(Commodity::Gold), Mass::troy_ounces(6.8e9));
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+        assert_eq!(config.assets(), vec!["ethereum", "bitcoin"]);
+        assert_eq!(config.output(), OutputFormat::Human);
+    }
+
+    #[test]
+    fn test_above_ground_rejects_unknown_commodity() {
+        let config: Config = toml::from_str("[above_ground]\ncopper = \"1t\"").unwrap();
+        assert!(matches!(config.above_ground(), Err(Error::Config(..))));
+    }
+
+    #[test]
+    fn test_template_parses() {
+        assert_eq!(
+            toml::from_str::<Config>(TEMPLATE).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn test_effective_config_redacts_api_keys() {
+        let config = Config {
+            coingecko: CoingeckoConfig {
+                api_key: Some(String::from("secret")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let effective = toml::to_string(&config.effective().unwrap()).unwrap();
+        assert!(!effective.contains("secret"));
+        assert!(effective.contains(REDACTED));
+        assert!(!format!("{config:?}").contains("secret"));
+    }
+
+    #[test]
+    fn test_coingecko_pro_plan_selects_pro_host() {
+        let config: Config =
+            toml::from_str("[coingecko]\napi_key = \"key\"\nplan = \"pro\"").unwrap();
+        assert_eq!(config.api_keys().coingecko_plan(), CoingeckoPlan::Pro);
+        if env::var_os("COINGECKO_BASE_URL").is_none() {
+            assert_eq!(
+                config.base_urls().unwrap().coingecko(),
+                "https://pro-api.coingecko.com"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rate_limits_default_to_plans() {
+        let config: Config =
+            toml::from_str("[polygon]\nrequests_per_minute = 100\n[coingecko]\nplan = \"pro\"")
+                .unwrap();
+        let [polygon, coingecko] = config.rate_limits();
+        assert_eq!(polygon.requests_per_minute, 100);
+        assert_eq!(coingecko.requests_per_minute, 500);
+    }
+
+    #[test]
+    fn test_retry_overrides_defaults() {
+        let config: Config =
+            toml::from_str("[retry.coingecko]\nmax_retries = 5\nmax_delay = \"2m\"").unwrap();
+        let coingecko = config.retry().unwrap().get("coingecko");
+        assert_eq!(coingecko.max_retries, 5);
+        assert_eq!(coingecko.base_delay, Duration::from_secs(2));
+        assert_eq!(coingecko.max_delay, Duration::from_secs(120));
+        let config: Config = toml::from_str("[retry.forex]\nbase_delay = \"5m\"").unwrap();
+        assert!(matches!(config.retry(), Err(Error::Config(..))));
+    }
+
+    #[test]
+    fn test_unknown_provider_keys_are_rejected() {
+        let config: Config = toml::from_str("[retry.coingeko]\nmax_retries = 5").unwrap();
+        assert!(matches!(config.retry(), Err(Error::Config(..))));
+        let config: Config =
+            toml::from_str("[cache]\ndir = \"/tmp/ratio-gang\"\n[cache.ttl]\npolgyon = \"1h\"")
+                .unwrap();
+        assert!(matches!(config.cache(), Err(Error::Config(..))));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("6h").unwrap(), Duration::from_secs(21600));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_invalid_values() {
+        assert!(matches!(
+            parse_duration("5 minutes"),
+            Err(Error::InvalidDuration(..))
+        ));
+        assert!(matches!(
+            parse_duration("m"),
+            Err(Error::InvalidDuration(..))
+        ));
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_overflow() {
+        assert!(matches!(
+            parse_duration("999999999999999999d"),
+            Err(Error::InvalidDuration(..))
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Days, Months, NaiveDate};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Daily market caps, sorted by date
+pub type Series = Vec<(NaiveDate, f64)>;
+
+/// Spacing between the points of a ratio history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" | "1d" => Ok(Interval::Day),
+            "week" | "1w" => Ok(Interval::Week),
+            "month" | "1mo" => Ok(Interval::Month),
+            _ => Err(format!("unknown interval '{s}', use day, week or month")),
+        }
+    }
+}
+
+/// Ratio between two market caps at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RatioPoint {
+    pub date: NaiveDate,
+    pub numerator: f64,
+    pub denominator: f64,
+    pub ratio: f64,
+}
+
+/// Dates from `from` to `to` (inclusive) spaced by `interval`
+pub fn grid(from: NaiveDate, to: NaiveDate, interval: Interval) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    let mut step = 0;
+    loop {
+        let date = match interval {
+            Interval::Day => from.checked_add_days(Days::new(step)),
+            Interval::Week => from.checked_add_days(Days::new(step * 7)),
+            Interval::Month => from.checked_add_months(Months::new(step as u32)),
+        };
+        match date {
+            Some(date) if date <= to => dates.push(date),
+            _ => return dates,
+        }
+        step += 1;
+    }
+}
+
+/// Reduces `[unix milliseconds, value]` pairs to the last value of each UTC day
+pub fn daily(points: &[(i64, f64)]) -> Series {
+    let mut series: Series = Vec::new();
+    for (timestamp, value) in points {
+        let date = match DateTime::from_timestamp_millis(*timestamp) {
+            Some(time) => time.date_naive(),
+            None => continue,
+        };
+        match series.last_mut() {
+            Some((last, last_value)) if *last == date => *last_value = *value,
+            _ => series.push((date, *value)),
+        }
+    }
+    series
+}
+
+/// Value of the series at `date`, which is the last observation on or before `date`.
+/// Carrying values forward aligns trading-day calendars of stocks and forex, where weekends
+/// and holidays have no data, with crypto markets that trade every day.
+pub fn value_at(series: &[(NaiveDate, f64)], date: NaiveDate) -> Option<f64> {
+    let index = series.partition_point(|(d, _)| *d <= date);
+    index.checked_sub(1).map(|i| series[i].1)
+}
+
+/// Ratio of `numerator` to `denominator` at every date of the grid where both have a value
+pub fn ratio_history(
+    numerator: &Series,
+    denominator: &Series,
+    dates: &[NaiveDate],
+) -> Vec<RatioPoint> {
+    dates
+        .iter()
+        .filter_map(|date| {
+            let n = value_at(numerator, *date)?;
+            let d = value_at(denominator, *date).filter(|d| *d > 0.0)?;
+            Some(RatioPoint {
+                date: *date,
+                numerator: n,
+                denominator: d,
+                ratio: n / d,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_grid_days() {
+        let dates = grid(date(2024, 2, 27), date(2024, 3, 1), Interval::Day);
+        assert_eq!(
+            dates,
+            vec![
+                date(2024, 2, 27),
+                date(2024, 2, 28),
+                date(2024, 2, 29),
+                date(2024, 3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grid_weeks_and_months() {
+        let weeks = grid(date(2024, 1, 1), date(2024, 1, 20), Interval::Week);
+        assert_eq!(
+            weeks,
+            vec![date(2024, 1, 1), date(2024, 1, 8), date(2024, 1, 15)]
+        );
+        let months = grid(date(2024, 1, 31), date(2024, 4, 30), Interval::Month);
+        assert_eq!(
+            months,
+            vec![
+                date(2024, 1, 31),
+                date(2024, 2, 29),
+                date(2024, 3, 31),
+                date(2024, 4, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interval_from_str() {
+        assert_eq!("week".parse::<Interval>().unwrap(), Interval::Week);
+        assert_eq!("1mo".parse::<Interval>().unwrap(), Interval::Month);
+        // 1m is a minute in durations elsewhere in the CLI
+        assert!("1m".parse::<Interval>().is_err());
+        assert!("fortnight".parse::<Interval>().is_err());
+    }
+
+    #[test]
+    fn test_daily_keeps_last_value_per_day() {
+        let series = daily(&[
+            (1704067200000, 1.0), // 2024-01-01 00:00
+            (1704110400000, 2.0), // 2024-01-01 12:00
+            (1704153600000, 3.0), // 2024-01-02 00:00
+        ]);
+        assert_eq!(
+            series,
+            vec![(date(2024, 1, 1), 2.0), (date(2024, 1, 2), 3.0)]
+        );
+    }
+
+    #[test]
+    fn test_ratio_history_carries_trading_days_over_weekends() {
+        // 2024-01-05 is a friday, stocks have no data on the weekend
+        let crypto = vec![
+            (date(2024, 1, 5), 50.0),
+            (date(2024, 1, 6), 60.0),
+            (date(2024, 1, 7), 70.0),
+            (date(2024, 1, 8), 80.0),
+        ];
+        let stock = vec![(date(2024, 1, 5), 100.0), (date(2024, 1, 8), 200.0)];
+        let dates = grid(date(2024, 1, 4), date(2024, 1, 8), Interval::Day);
+        let ratios: Vec<(NaiveDate, f64)> = ratio_history(&crypto, &stock, &dates)
+            .iter()
+            .map(|p| (p.date, p.ratio))
+            .collect();
+        assert_eq!(
+            ratios,
+            vec![
+                (date(2024, 1, 5), 0.5),
+                (date(2024, 1, 6), 0.6),
+                (date(2024, 1, 7), 0.7),
+                (date(2024, 1, 8), 0.4),
+            ]
+        );
+    }
+}
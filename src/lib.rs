@@ -1,13 +1,50 @@
 use anyhow::Result;
-use reqwest::header::USER_AGENT;
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use futures::future::try_join_all;
+use provider::{COINGECKO, COMMODITY, FOREX, POLYGON};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
+use transport::Source;
 use url::{ParseError, Url};
 
-const POLYGONIO_BASE_URL: &str = "https://api.polygon.io";
-const COINGECKO_BASE_URL: &str = "https://api.coingecko.com";
-const TONNE_TO_OUNCE: f64 = 35273.96194958;
+// lets fixtures shared with the tests of the binary name the crate the same way in both
+#[cfg(test)]
+extern crate self as ratio_gang_cli;
+
+pub mod alert;
+pub mod cache;
+pub mod coins;
+pub mod commodity;
+pub mod compare;
+pub mod config;
+pub mod history;
+pub mod provider;
+pub mod ratelimit;
+pub mod retry;
+pub mod search;
+#[cfg(test)]
+mod testing;
+pub mod transport;
+pub mod units;
+
+pub use commodity::{AboveGround, Commodity};
+pub use history::Series;
+pub use provider::{
+    classify, identify_market_cap_type, MarketCapProvider, MarketCapType, ProviderRegistry, Quote,
+    QuoteOptions, RateLimit,
+};
+pub use transport::Transport;
+pub use units::{Mass, MassUnit};
+
+pub const POLYGONIO_BASE_URL: &str = "https://api.polygon.io";
+pub const COINGECKO_BASE_URL: &str = "https://api.coingecko.com";
+pub const COINGECKO_PRO_BASE_URL: &str = "https://pro-api.coingecko.com";
+/// Coins per `/coins/markets` request, the default page size of the endpoint
+pub const COINGECKO_MARKETS_PAGE_SIZE: usize = 100;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -17,16 +54,103 @@ pub enum Error {
     SendRequest(reqwest::Error),
     #[error("Failed to deserialize response: '{0}' for asset {1}\n")]
     Deserialization(serde_json::Error, String),
-    #[error("Unexpected status code: {0}\n")]
-    UnexpectedStatus(reqwest::Error),
-    #[error("Required environmental variable not set. Use 'export {0}=YOURKEY' to set it.\n")]
+    #[error("Required environmental variable not set. Use 'export {0}=YOURKEY' to set it or add api_key to the config file.\n")]
     EnvVarError(String),
     #[error("Polygon API error: {0}\n")]
     PolygonApi(String),
+    #[error("Polygon API error: {0}\n")]
+    TickerNotFound(String),
     #[error("Coingecko API did not return expected payload.\nReceived {0}, expected https://docs.coingecko.com/reference/coins-markets\n")]
     CoingeckoApi(String),
-    #[error("Could not identify if {0} is a crypto asset or a stock, please use all caps for stock symbols and lower caps for crypto coingecko-ids\n")]
+    #[error("Coingecko only serves market caps of the past 365 days on the public and demo plans, use a later date or a pro plan API key\n")]
+    CoingeckoHistoryLimit,
+    #[error("Could not identify if {0} is a crypto asset, a stock or a commodity, please use a prefix like stock:GOLD, crypto:bitcoin or commodity:gold\n")]
     UnknownAssetName(String),
+    #[error("Could not find {0}, tried {1}\n")]
+    AssetNotFound(String, String),
+    #[error("No coin on coingecko has the id, symbol or name {0}\n")]
+    UnknownCoin(String),
+    #[error("Invalid config file {}: {1}\n", .0.display())]
+    Config(PathBuf, String),
+    #[error("Could not access fixture {}: {1}\n", .0.display())]
+    Fixture(PathBuf, String),
+    #[error("Invalid duration '{0}', use e.g. 90s, 15m, 6h or 1d\n")]
+    InvalidDuration(String),
+    #[error("No market data available for {0}\n")]
+    NoData(String),
+    #[error("No market cap history available for {0}\n")]
+    HistoryUnsupported(String),
+    #[error("Cannot convert market caps to {0} without an exchange rate source\n")]
+    NoExchangeRate(String),
+    #[error("Could not access alert state {}: {1}\n", .0.display())]
+    AlertState(PathBuf, String),
+    #[error("Rate limit of {0} exceeded, {}\n", retry_hint(.1))]
+    RateLimited(String, Option<Duration>),
+    #[error("Temporary failure of {0}: {1}, try again later\n")]
+    Transient(String, String),
+}
+
+impl Error {
+    /// Whether the request may succeed when sent again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::RateLimited(..) | Error::Transient(..))
+    }
+
+    /// Whether the API doesn't know the asset, which may still be found as another type
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Error::TickerNotFound(..)
+                | Error::UnknownCoin(..)
+                | Error::NoData(..)
+                | Error::UnknownAssetName(..)
+        )
+    }
+}
+
+fn retry_hint(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(delay) => format!("try again in {}s", delay.as_secs().max(1)),
+        None => String::from("try again later"),
+    }
+}
+
+/// API base URLs, e.g. to point the client at a local mock server or a proxy mirror
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseUrls {
+    polygon: String,
+    coingecko: String,
+}
+
+impl Default for BaseUrls {
+    fn default() -> Self {
+        Self {
+            polygon: String::from(POLYGONIO_BASE_URL),
+            coingecko: String::from(COINGECKO_BASE_URL),
+        }
+    }
+}
+
+impl BaseUrls {
+    pub fn new(polygon: &str, coingecko: &str) -> Result<BaseUrls, Error> {
+        Ok(Self {
+            polygon: normalize_base_url(polygon)?,
+            coingecko: normalize_base_url(coingecko)?,
+        })
+    }
+
+    pub fn polygon(&self) -> &str {
+        &self.polygon
+    }
+
+    pub fn coingecko(&self) -> &str {
+        &self.coingecko
+    }
+}
+
+fn normalize_base_url(base_url: &str) -> Result<String, Error> {
+    Url::parse(base_url).map_err(Error::InvalidUrl)?;
+    Ok(base_url.trim_end_matches('/').to_string())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -93,7 +217,7 @@ struct CompanyDetails {
     list_date: Option<String>,
     locale: String,
     market: String,
-    market_cap: f64,
+    market_cap: Option<f64>,
     name: String,
     phone_number: String,
     primary_exchange: String,
@@ -117,6 +241,7 @@ struct TickerDetailsV3 {
     results: CompanyDetails,
 }
 
+/// Previous day (`/prev`) and range (`/range`) aggregates
 #[derive(Serialize, Deserialize, Debug)]
 struct AggsTickerV2 {
     ticker: String,
@@ -125,26 +250,60 @@ struct AggsTickerV2 {
     #[serde(rename(serialize = "resultsCount", deserialize = "resultsCount"))]
     results_count: u32,
     adjusted: bool,
+    // omitted when there are no results, e.g. for a range over a weekend
+    #[serde(default)]
     results: Vec<OHCL>,
     status: String,
     request_id: String,
-    count: u32,
+    // only returned by /prev
+    count: Option<u32>,
 }
 
-/// Previous day's open, high, low, and close (OHCL)
+/// Open, high, low, and close (OHCL) of one aggregate window
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Serialize, Deserialize, Debug)]
 struct OHCL {
+    // only returned by /prev
     #[serde(rename(serialize = "T", deserialize = "T"))]
-    ticker: String,
-    v: u32,
-    vw: f64,
+    ticker: Option<String>,
+    v: f64,
+    vw: Option<f64>,
     o: f64,
     c: f64,
     h: f64,
     l: f64,
     #[serde(rename(serialize = "t", deserialize = "t"))]
     timestamp: u64,
-    n: u32,
+    n: Option<u32>,
+}
+
+/// The fields of `/coins/markets` needed for market caps, `market_cap` is null for some coins
+#[derive(Deserialize, Debug)]
+struct CoingeckoMarketCapV3 {
+    id: String,
+    market_cap: Option<f64>,
+}
+
+/// Coin data at a past date, `market_data` is missing for dates before the coin was listed
+#[derive(Serialize, Deserialize, Debug)]
+struct CoingeckoHistoryV3 {
+    id: String,
+    symbol: String,
+    name: String,
+    market_data: Option<CoingeckoHistoryMarketData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CoingeckoHistoryMarketData {
+    market_cap: HashMap<String, f64>,
+}
+
+/// `[unix timestamp in milliseconds, value]` pairs
+#[derive(Serialize, Deserialize, Debug)]
+struct CoingeckoMarketChartV3 {
+    prices: Vec<(i64, f64)>,
+    market_caps: Vec<(i64, f64)>,
+    total_volumes: Vec<(i64, f64)>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -154,156 +313,599 @@ struct PolygonIoErrorResponse {
     message: String,
 }
 
-// holds api keys from system env
-#[derive(Debug, Default)]
+/// coingecko API plan, selects the host, how the key is sent and the rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoingeckoPlan {
+    /// No key
+    #[default]
+    Public,
+    /// Free demo key, sent in the `x-cg-demo-api-key` header
+    Demo,
+    /// Paid key for pro-api.coingecko.com, sent in the `x-cg-pro-api-key` header
+    Pro,
+}
+
+impl CoingeckoPlan {
+    pub fn base_url(&self) -> &'static str {
+        match self {
+            CoingeckoPlan::Public | CoingeckoPlan::Demo => COINGECKO_BASE_URL,
+            CoingeckoPlan::Pro => COINGECKO_PRO_BASE_URL,
+        }
+    }
+
+    fn header(&self) -> Option<&'static str> {
+        match self {
+            CoingeckoPlan::Public => None,
+            CoingeckoPlan::Demo => Some("x-cg-demo-api-key"),
+            CoingeckoPlan::Pro => Some("x-cg-pro-api-key"),
+        }
+    }
+
+    /// Documented rate limit of the plan, the public API varies with load and is lower at times
+    pub fn requests_per_minute(&self) -> u32 {
+        match self {
+            CoingeckoPlan::Public => 10,
+            CoingeckoPlan::Demo => 30,
+            CoingeckoPlan::Pro => 500,
+        }
+    }
+}
+
+/// API keys of the providers, a missing key is only reported once a provider needs it
+#[derive(Default, Clone)]
 pub struct ApiKeys {
-    coingecko: String,
-    polygonio: String,
+    coingecko: Option<String>,
+    coingecko_plan: CoingeckoPlan,
+    polygonio: Option<String>,
 }
 
-pub fn get_required_envs() -> Result<ApiKeys, Error> {
-    let mut apikeys = ApiKeys::default();
-    let polygon_env = String::from("POLYGON_KEY");
-    let coingecko_env = String::from("COINGECKO_KEY");
+/// Shows whether a key is set, never the key
+impl fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |key: &Option<String>| key.as_ref().map(|_| "********");
+        f.debug_struct("ApiKeys")
+            .field("coingecko", &redacted(&self.coingecko))
+            .field("coingecko_plan", &self.coingecko_plan)
+            .field("polygonio", &redacted(&self.polygonio))
+            .finish()
+    }
+}
+
+impl ApiKeys {
+    /// A coingecko key is taken to be a demo key unless the plan is set with
+    /// [`ApiKeys::with_coingecko_plan`]
+    pub fn new(polygonio: Option<String>, coingecko: Option<String>) -> Self {
+        let coingecko_plan = match coingecko {
+            Some(_) => CoingeckoPlan::Demo,
+            None => CoingeckoPlan::Public,
+        };
+        Self {
+            coingecko,
+            coingecko_plan,
+            polygonio,
+        }
+    }
+
+    pub fn has_polygonio(&self) -> bool {
+        self.polygonio.is_some()
+    }
+
+    pub fn with_coingecko_plan(mut self, plan: CoingeckoPlan) -> Self {
+        self.coingecko_plan = plan;
+        self
+    }
+
+    pub fn coingecko_plan(&self) -> CoingeckoPlan {
+        self.coingecko_plan
+    }
 
-    match env::var(&polygon_env) {
-        Ok(value) => apikeys.polygonio = value,
-        Err(_) => {
-            return Err(Error::EnvVarError(polygon_env));
+    /// `Authorization` header of polygon.io requests, polygon.io requires a key for every request
+    fn polygonio_authorization(&self) -> Result<String, Error> {
+        match self.polygonio.as_deref() {
+            Some(key) => Ok(format!("Bearer {key}")),
+            None => Err(Error::EnvVarError(String::from("POLYGON_KEY"))),
         }
     }
-    match env::var(&coingecko_env) {
-        Ok(value) => apikeys.coingecko = value,
-        Err(_) => {
-            return Err(Error::EnvVarError(coingecko_env));
+
+    /// Authentication header of coingecko requests, none for the public API
+    fn coingecko_header(&self) -> Result<Option<(&'static str, &str)>, Error> {
+        match self.coingecko_plan.header() {
+            Some(header) => match self.coingecko.as_deref() {
+                Some(key) => Ok(Some((header, key))),
+                None => Err(Error::EnvVarError(String::from("COINGECKO_KEY"))),
+            },
+            None => Ok(None),
         }
     }
-    Ok(apikeys)
 }
 
+/// Markets of coins with prices and market caps in `currency`, `coingecko_ids` is a comma
+/// separated list of up to [`COINGECKO_MARKETS_PAGE_SIZE`] ids
 fn construct_coingecko_v3_markets_query_url(
-    coingecko_id: &String,
-    apikey: &String,
+    base_url: &str,
+    coingecko_ids: &str,
+    currency: &str,
 ) -> Result<Url, Error> {
-    match Url::from_str(&format!("{COINGECKO_BASE_URL}/api/v3/coins/markets")) {
+    match Url::from_str(&format!("{base_url}/api/v3/coins/markets")) {
         Ok(mut url) => {
             url.query_pairs_mut()
-                .append_pair("vs_currency", "usd")
-                .append_pair("ids", &coingecko_id)
-                .append_pair("x_cg_key", &apikey);
-            return Ok(url);
+                .append_pair("vs_currency", &currency.to_lowercase())
+                .append_pair("ids", coingecko_ids);
+            Ok(url)
         }
-        Err(error) => return Err(Error::InvalidUrl(error)),
+        Err(error) => Err(Error::InvalidUrl(error)),
     }
 }
 
-fn construct_tickerdetailsv3_query_url(
-    stock_symbol: &String,
-    apikey: &String,
+fn construct_coingecko_v3_history_query_url(
+    base_url: &str,
+    coingecko_id: &str,
+    date: NaiveDate,
+) -> Result<Url, Error> {
+    match Url::from_str(&format!("{base_url}/api/v3/coins/{coingecko_id}/history")) {
+        Ok(mut url) => {
+            url.query_pairs_mut()
+                .append_pair("date", &date.format("%d-%m-%Y").to_string())
+                .append_pair("localization", "false");
+            Ok(url)
+        }
+        Err(error) => Err(Error::InvalidUrl(error)),
+    }
+}
+
+/// Market caps between 00:00 UTC of `from` and the end of `to`
+fn construct_coingecko_v3_market_chart_range_query_url(
+    base_url: &str,
+    coingecko_id: &str,
+    from: NaiveDate,
+    to: NaiveDate,
 ) -> Result<Url, Error> {
+    let from = from.and_time(NaiveTime::MIN).and_utc().timestamp();
+    let to = (to + Days::new(1))
+        .and_time(NaiveTime::MIN)
+        .and_utc()
+        .timestamp()
+        - 1;
     match Url::from_str(&format!(
-        "{POLYGONIO_BASE_URL}/v3/reference/tickers/{stock_symbol}"
+        "{base_url}/api/v3/coins/{coingecko_id}/market_chart/range"
     )) {
         Ok(mut url) => {
-            url.query_pairs_mut().append_pair("apiKey", &apikey);
-            return Ok(url);
+            url.query_pairs_mut()
+                .append_pair("vs_currency", "usd")
+                .append_pair("from", &from.to_string())
+                .append_pair("to", &to.to_string());
+            Ok(url)
         }
-        Err(error) => return Err(Error::InvalidUrl(error)),
+        Err(error) => Err(Error::InvalidUrl(error)),
     }
 }
 
-fn construct_forex_query_url(forex_ticker: &String, apikey: &String) -> Result<Url, Error> {
+/// Ticker details, as of `date` if set
+fn construct_tickerdetailsv3_query_url(
+    base_url: &str,
+    stock_symbol: &str,
+    date: Option<NaiveDate>,
+) -> Result<Url, Error> {
+    match Url::from_str(&format!("{base_url}/v3/reference/tickers/{stock_symbol}")) {
+        Ok(mut url) => {
+            if let Some(date) = date {
+                url.query_pairs_mut()
+                    .append_pair("date", &date.format("%Y-%m-%d").to_string());
+            }
+            Ok(url)
+        }
+        Err(error) => Err(Error::InvalidUrl(error)),
+    }
+}
+
+fn construct_forex_query_url(base_url: &str, forex_ticker: &str) -> Result<Url, Error> {
+    Url::from_str(&format!("{base_url}/v2/aggs/ticker/C:{forex_ticker}/prev"))
+        .map_err(Error::InvalidUrl)
+}
+
+/// Daily aggregates between `from` and `to`, forex tickers are prefixed with `C:`
+fn construct_range_aggs_query_url(
+    base_url: &str,
+    ticker: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Url, Error> {
     match Url::from_str(&format!(
-        "{POLYGONIO_BASE_URL}/v2/aggs/ticker/C:{forex_ticker}/prev"
+        "{base_url}/v2/aggs/ticker/{ticker}/range/1/day/{}/{}",
+        from.format("%Y-%m-%d"),
+        to.format("%Y-%m-%d")
     )) {
         Ok(mut url) => {
-            url.query_pairs_mut().append_pair("apiKey", &apikey);
-            return Ok(url);
+            url.query_pairs_mut().append_pair("sort", "asc");
+            Ok(url)
         }
-        Err(error) => return Err(Error::InvalidUrl(error)),
+        Err(error) => Err(Error::InvalidUrl(error)),
     }
 }
 
-pub async fn return_stock_market_cap(
-    client: &reqwest::Client,
-    stock_symbol: &String,
+async fn fetch_ticker_details(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    stock_symbol: &str,
+    date: Option<NaiveDate>,
     apikeys: &ApiKeys,
-) -> Result<f64, anyhow::Error> {
-    let url = construct_tickerdetailsv3_query_url(&stock_symbol, &apikeys.polygonio)?;
-    let response = client
-        .get(url)
-        .header("Accept", "application/json")
-        .send()
-        .await
-        .map_err(Error::SendRequest)?;
-
-    if response.status().is_success() {
-        let body = response.text().await?;
+) -> Result<CompanyDetails> {
+    let url = construct_tickerdetailsv3_query_url(&base_urls.polygon, stock_symbol, date)?;
+    let authorization = apikeys.polygonio_authorization()?;
+    let response = transport
+        .get_with_headers(POLYGON, url, &[("Authorization", &authorization)])
+        .await?;
+
+    if response.status.is_success() {
+        let body = response.body;
         let ticker_details_v3: TickerDetailsV3 = serde_json::from_str(&body)
-            .map_err(|e| Error::Deserialization(e, stock_symbol.clone()))?;
-        Ok(ticker_details_v3.results.market_cap)
+            .map_err(|e| Error::Deserialization(e, stock_symbol.to_string()))?;
+        Ok(ticker_details_v3.results)
     } else {
-        let body = response.text().await?;
+        let body = response.body;
         let error_json: PolygonIoErrorResponse = serde_json::from_str(&body)
-            .map_err(|e| Error::Deserialization(e, stock_symbol.clone()))?;
-        return Err(Error::PolygonApi(error_json.message).into());
+            .map_err(|e| Error::Deserialization(e, stock_symbol.to_string()))?;
+        if response.status == reqwest::StatusCode::NOT_FOUND {
+            return Err(Error::TickerNotFound(error_json.message).into());
+        }
+        Err(Error::PolygonApi(error_json.message).into())
+    }
+}
+
+async fn fetch_range_aggs(
+    transport: &Transport,
+    source: Source,
+    base_urls: &BaseUrls,
+    ticker: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    apikeys: &ApiKeys,
+) -> Result<Vec<OHCL>> {
+    let url = construct_range_aggs_query_url(&base_urls.polygon, ticker, from, to)?;
+    let authorization = apikeys.polygonio_authorization()?;
+    let response = transport
+        .get_with_headers(source, url, &[("Authorization", &authorization)])
+        .await?;
+
+    if response.status.is_success() {
+        let aggs_ticker_v2: AggsTickerV2 = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, ticker.to_string()))?;
+        Ok(aggs_ticker_v2.results)
+    } else {
+        let error_json: PolygonIoErrorResponse = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, ticker.to_string()))?;
+        Err(Error::PolygonApi(error_json.message).into())
+    }
+}
+
+/// Date of an aggregate window start given in unix milliseconds
+fn aggregate_date(ohcl: &OHCL) -> Option<NaiveDate> {
+    DateTime::from_timestamp_millis(ohcl.timestamp as i64).map(|t| t.date_naive())
+}
+
+/// Market cap of a stock, as of `date` if set
+pub async fn return_stock_market_cap(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    stock_symbol: &str,
+    date: Option<NaiveDate>,
+    apikeys: &ApiKeys,
+) -> Result<f64, anyhow::Error> {
+    let details = fetch_ticker_details(transport, base_urls, stock_symbol, date, apikeys).await?;
+    match details.market_cap {
+        Some(market_cap) => Ok(market_cap),
+        None => Err(Error::NoData(stock_symbol.to_string()).into()),
     }
 }
 
-pub async fn return_gold_market_cap(
-    client: &reqwest::Client,
-    above_ground: &f64,
+/// Daily market caps of a stock between `from` and `to`, estimated as the close times the
+/// weighted shares outstanding as of `to`
+pub async fn return_stock_market_cap_history(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    stock_symbol: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    apikeys: &ApiKeys,
+) -> Result<Series> {
+    let (details, aggs) = tokio::try_join!(
+        fetch_ticker_details(transport, base_urls, stock_symbol, Some(to), apikeys),
+        fetch_range_aggs(
+            transport,
+            POLYGON,
+            base_urls,
+            stock_symbol,
+            from,
+            to,
+            apikeys
+        ),
+    )?;
+    let shares = details.weighted_shares_outstanding as f64;
+    Ok(aggs
+        .iter()
+        .filter_map(|ohcl| Some((aggregate_date(ohcl)?, ohcl.c * shares)))
+        .collect())
+}
+
+/// Value of the above ground stock at a price in USD per troy ounce, which is how the
+/// polygon.io forex pairs of precious metals are quoted
+fn commodity_market_cap(price: f64, above_ground: Mass) -> f64 {
+    price * above_ground.get(MassUnit::TroyOunce)
+}
+
+/// Market cap of a commodity from the previous day's close, or from the last close on or before
+/// `date` if set. Forex markets are closed on weekends so up to a week is looked back.
+pub async fn return_commodity_market_cap(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    commodity: Commodity,
+    above_ground: Mass,
+    date: Option<NaiveDate>,
     apikeys: &ApiKeys,
 ) -> Result<f64> {
-    let gold_ticker = String::from("XAUUSD");
-    let url = construct_forex_query_url(&gold_ticker, &apikeys.polygonio)?;
-    let response = client
-        .get(url)
-        .header("Accept", "application/json")
-        .send()
-        .await
-        .map_err(Error::SendRequest)?;
-
-    if response.status().is_success() {
-        let body = response.text().await?;
-        let aggs_ticker_v2: AggsTickerV2 =
-            serde_json::from_str(&body).map_err(|e| Error::Deserialization(e, gold_ticker))?;
-        let gold_market_cap: f64 = aggs_ticker_v2.results[0].c * above_ground * TONNE_TO_OUNCE;
-        Ok(gold_market_cap)
+    let ticker = commodity.ticker();
+    let url = match date {
+        Some(date) => construct_range_aggs_query_url(
+            &base_urls.polygon,
+            &format!("C:{ticker}"),
+            date - Days::new(7),
+            date,
+        )?,
+        None => construct_forex_query_url(&base_urls.polygon, ticker)?,
+    };
+    let authorization = apikeys.polygonio_authorization()?;
+    let response = transport
+        .get_with_headers(COMMODITY, url, &[("Authorization", &authorization)])
+        .await?;
+
+    if response.status.is_success() {
+        let body = response.body;
+        let aggs_ticker_v2: AggsTickerV2 = serde_json::from_str(&body)
+            .map_err(|e| Error::Deserialization(e, ticker.to_string()))?;
+        match aggs_ticker_v2.results.last() {
+            Some(ohcl) => Ok(commodity_market_cap(ohcl.c, above_ground)),
+            None => Err(Error::NoData(ticker.to_string()).into()),
+        }
+    } else {
+        let body = response.body;
+        let error_json: PolygonIoErrorResponse = serde_json::from_str(&body)
+            .map_err(|e| Error::Deserialization(e, ticker.to_string()))?;
+        Err(Error::PolygonApi(error_json.message).into())
+    }
+}
+
+/// Daily market caps of a commodity between `from` and `to` from the closes of its forex pair
+pub async fn return_commodity_market_cap_history(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    commodity: Commodity,
+    above_ground: Mass,
+    from: NaiveDate,
+    to: NaiveDate,
+    apikeys: &ApiKeys,
+) -> Result<Series> {
+    let ticker = format!("C:{}", commodity.ticker());
+    let aggs =
+        fetch_range_aggs(transport, COMMODITY, base_urls, &ticker, from, to, apikeys).await?;
+    Ok(aggs
+        .iter()
+        .filter_map(|ohcl| {
+            Some((
+                aggregate_date(ohcl)?,
+                commodity_market_cap(ohcl.c, above_ground),
+            ))
+        })
+        .collect())
+}
+
+/// Price of one US dollar in another currency
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExchangeRate {
+    pub currency: String,
+    pub rate: f64,
+    /// Timestamp of the aggregate whose close is the rate
+    pub time: DateTime<Utc>,
+}
+
+/// Previous day's close of USD in `currency`, or the last close on or before `date` if set
+pub async fn return_exchange_rate(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    currency: &str,
+    date: Option<NaiveDate>,
+    apikeys: &ApiKeys,
+) -> Result<ExchangeRate> {
+    let ticker = format!("USD{currency}");
+    let url = match date {
+        Some(date) => construct_range_aggs_query_url(
+            &base_urls.polygon,
+            &format!("C:{ticker}"),
+            date - Days::new(7),
+            date,
+        )?,
+        None => construct_forex_query_url(&base_urls.polygon, &ticker)?,
+    };
+    let authorization = apikeys.polygonio_authorization()?;
+    let response = transport
+        .get_with_headers(FOREX, url, &[("Authorization", &authorization)])
+        .await?;
+
+    if response.status.is_success() {
+        let aggs_ticker_v2: AggsTickerV2 = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, ticker.clone()))?;
+        let ohcl = aggs_ticker_v2
+            .results
+            .last()
+            .ok_or_else(|| Error::NoData(ticker.clone()))?;
+        let time = DateTime::from_timestamp_millis(ohcl.timestamp as i64)
+            .ok_or_else(|| Error::NoData(ticker.clone()))?;
+        Ok(ExchangeRate {
+            currency: currency.to_string(),
+            rate: ohcl.c,
+            time,
+        })
     } else {
-        let body = response.text().await?;
         let error_json: PolygonIoErrorResponse =
-            serde_json::from_str(&body).map_err(|e| Error::Deserialization(e, gold_ticker))?;
-        return Err(Error::PolygonApi(error_json.message).into());
+            serde_json::from_str(&response.body).map_err(|e| Error::Deserialization(e, ticker))?;
+        Err(Error::PolygonApi(error_json.message).into())
     }
 }
 
+/// Current market cap of a cryptocurrency in `currency`, or at 00:00 UTC of `date` if set.
+/// `asset` is a coingecko id or a symbol or name like `BTC`, see [`coins::resolve`].
 pub async fn return_cyrpto_market_cap(
-    client: &reqwest::Client,
-    coingecko_id: &String,
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    asset: &str,
+    date: Option<NaiveDate>,
+    currency: &str,
     apikeys: &ApiKeys,
 ) -> Result<f64> {
-    let url = construct_coingecko_v3_markets_query_url(&coingecko_id, &apikeys.coingecko)?;
-    let response = client
-        .get(url)
-        .header("Accept", "application/json")
-        .header("User-Agent", USER_AGENT)
-        .send()
-        .await
-        .map_err(Error::SendRequest)?;
-
-    if response.status().is_success() {
-        let body = response.text().await?;
-        // []
-        if body == "[]" {
-            return Err(Error::CoingeckoApi(body).into());
-        }
-        let coingecko_markets_v3: Vec<CoingeckoMarketsV2> = serde_json::from_str(&body)
-            .map_err(|e| Error::Deserialization(e, coingecko_id.clone()))?;
-        Ok(coingecko_markets_v3[0].market_cap)
+    let coin = coins::resolve(transport, base_urls, asset, apikeys).await?;
+    let coingecko_id = coin.id.as_str();
+    if let Some(date) = date {
+        return return_historical_crypto_market_cap(
+            transport,
+            base_urls,
+            coingecko_id,
+            date,
+            currency,
+            apikeys,
+        )
+        .await;
+    }
+    let market_caps =
+        return_crypto_market_caps(transport, base_urls, &[coingecko_id], currency, apikeys).await?;
+    market_caps
+        .get(coingecko_id)
+        .copied()
+        .ok_or_else(|| Error::NoData(coingecko_id.to_string()).into())
+}
+
+/// Current market caps of many cryptocurrencies in `currency` by coingecko id, fetched with one
+/// `/coins/markets` request per [`COINGECKO_MARKETS_PAGE_SIZE`] ids. Coins unknown to coingecko
+/// or without a market cap are left out.
+pub async fn return_crypto_market_caps(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    coingecko_ids: &[&str],
+    currency: &str,
+    apikeys: &ApiKeys,
+) -> Result<HashMap<String, f64>> {
+    // sorted so that the same coins share a cache entry regardless of their order
+    let mut ids = coingecko_ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    let pages = try_join_all(
+        ids.chunks(COINGECKO_MARKETS_PAGE_SIZE)
+            .map(|page| return_crypto_markets_page(transport, base_urls, page, currency, apikeys)),
+    )
+    .await?;
+    Ok(pages
+        .into_iter()
+        .flatten()
+        .filter_map(|market| Some((market.id, market.market_cap?)))
+        .collect())
+}
+
+async fn return_crypto_markets_page(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    coingecko_ids: &[&str],
+    currency: &str,
+    apikeys: &ApiKeys,
+) -> Result<Vec<CoingeckoMarketCapV3>> {
+    let ids = coingecko_ids.join(",");
+    let url = construct_coingecko_v3_markets_query_url(&base_urls.coingecko, &ids, currency)?;
+    let header = apikeys.coingecko_header()?;
+    let response = transport
+        .get_with_headers(COINGECKO, url, header.as_slice())
+        .await?;
+    if response.status.is_success() {
+        Ok(serde_json::from_str(&response.body).map_err(|e| Error::Deserialization(e, ids))?)
+    } else {
+        Err(coingecko_error(response.body).into())
+    }
+}
+
+/// Market cap of the coin with `coingecko_id` in `currency` at 00:00 UTC of `date`
+pub async fn return_historical_crypto_market_cap(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    coingecko_id: &str,
+    date: NaiveDate,
+    currency: &str,
+    apikeys: &ApiKeys,
+) -> Result<f64> {
+    let url = construct_coingecko_v3_history_query_url(&base_urls.coingecko, coingecko_id, date)?;
+    let header = apikeys.coingecko_header()?;
+    let response = transport
+        .get_with_headers(COINGECKO, url, header.as_slice())
+        .await?;
+
+    if response.status.is_success() {
+        let history: CoingeckoHistoryV3 = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, coingecko_id.to_string()))?;
+        history
+            .market_data
+            .and_then(|market_data| {
+                let currency = currency.to_lowercase();
+                market_data.market_cap.get(&currency).copied()
+            })
+            .filter(|market_cap| *market_cap > 0.0)
+            .ok_or_else(|| Error::NoData(format!("{coingecko_id} on {date}")).into())
+    } else {
+        Err(coingecko_error(response.body).into())
+    }
+}
+
+/// coingecko's error code for a date beyond the history of the plan
+const COINGECKO_TIME_RANGE_ERROR: u64 = 10012;
+
+/// Error for an unsuccessful coingecko response, public and demo plans reject dates older than
+/// 365 days
+fn coingecko_error(body: String) -> Error {
+    let value: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+    let code = value
+        .pointer("/error/status/error_code")
+        .or_else(|| value.pointer("/status/error_code"))
+        .and_then(|code| code.as_u64());
+    match code {
+        Some(COINGECKO_TIME_RANGE_ERROR) => Error::CoingeckoHistoryLimit,
+        _ => Error::CoingeckoApi(body),
+    }
+}
+
+/// Daily market caps of a cryptocurrency between `from` and `to`, the last value of each day.
+/// `asset` is a coingecko id or a symbol or name like `BTC`.
+pub async fn return_crypto_market_cap_history(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    asset: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    apikeys: &ApiKeys,
+) -> Result<Series> {
+    let coin = coins::resolve(transport, base_urls, asset, apikeys).await?;
+    let coingecko_id = coin.id.as_str();
+    let url = construct_coingecko_v3_market_chart_range_query_url(
+        &base_urls.coingecko,
+        coingecko_id,
+        from,
+        to,
+    )?;
+    let header = apikeys.coingecko_header()?;
+    let response = transport
+        .get_with_headers(COINGECKO, url, header.as_slice())
+        .await?;
+
+    if response.status.is_success() {
+        let market_chart: CoingeckoMarketChartV3 = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, coingecko_id.to_string()))?;
+        Ok(history::daily(&market_chart.market_caps))
     } else {
-        let body = response.text().await?;
-        return Err(Error::CoingeckoApi(body).into());
+        Err(coingecko_error(response.body).into())
     }
 }
 
@@ -311,68 +913,153 @@ pub async fn return_cyrpto_market_cap(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_commodity_market_cap_uses_troy_ounces() {
+        // 1 tonne at 2000 USD per troy ounce
+        let market_cap = commodity_market_cap(2000.0, Mass::tonnes(1.0));
+        assert!((market_cap - 64_301_493.2).abs() < 0.1, "{market_cap}");
+        assert_eq!(
+            commodity_market_cap(2000.0, Mass::troy_ounces(10.0)),
+            20000.0
+        );
+    }
+
     #[test]
     fn test_construct_forex_query_url() {
-        let apikeys = ApiKeys {
-            coingecko: String::from("myCoinGeckoKey"),
-            polygonio: String::from("myPolygonIOKey"),
-        };
         let forex_ticker = &String::from("XAUUSD");
-        let constructed_url = construct_forex_query_url(&forex_ticker, &apikeys.polygonio).unwrap();
-        let target_url =
-            Url::parse("https://api.polygon.io/v2/aggs/ticker/C:XAUUSD/prev?apiKey=myPolygonIOKey")
-                .unwrap();
+        let constructed_url = construct_forex_query_url(POLYGONIO_BASE_URL, forex_ticker).unwrap();
+        let target_url = Url::parse("https://api.polygon.io/v2/aggs/ticker/C:XAUUSD/prev").unwrap();
         assert_eq!(constructed_url, target_url);
     }
 
     #[test]
     fn test_construct_tickerdetailsv3_query_url() {
-        let apikeys = ApiKeys {
-            coingecko: String::from("myCoinGeckoKey"),
-            polygonio: String::from("myPolygonIOKey"),
-        };
         let stock_symbol = &String::from("AAPL");
         let constructed_url =
-            construct_tickerdetailsv3_query_url(&stock_symbol, &apikeys.polygonio).unwrap();
-        let target_url =
-            Url::parse("https://api.polygon.io/v3/reference/tickers/AAPL?apiKey=myPolygonIOKey")
-                .unwrap();
+            construct_tickerdetailsv3_query_url(POLYGONIO_BASE_URL, stock_symbol, None).unwrap();
+        let target_url = Url::parse("https://api.polygon.io/v3/reference/tickers/AAPL").unwrap();
         assert_eq!(constructed_url, target_url);
     }
 
     #[test]
     fn test_construct_coingecko_v3_markets_query_url() {
-        let apikeys = ApiKeys {
-            coingecko: String::from("myCoinGeckoKey"),
-            polygonio: String::from("myPolygonIOKey"),
-        };
         let coingecko_id = &String::from("ethereum");
         let constructed_url =
-            construct_coingecko_v3_markets_query_url(&coingecko_id, &apikeys.coingecko).unwrap();
-        let target_url =
-            Url::parse("https://api.coingecko.com/api/v3/coins/markets?vs_currency=usd&ids=ethereum&x_cg_key=myCoinGeckoKey")
+            construct_coingecko_v3_markets_query_url(COINGECKO_BASE_URL, coingecko_id, "USD")
                 .unwrap();
+        let target_url = Url::parse(
+            "https://api.coingecko.com/api/v3/coins/markets?vs_currency=usd&ids=ethereum",
+        )
+        .unwrap();
         assert_eq!(constructed_url, target_url);
     }
 
     #[test]
-    fn test_get_required_envs_returns_keys_if_set() {
-        env::set_var("COINGECKO_KEY", "foo");
-        env::set_var("POLYGON_KEY", "bar");
-        let api_keys = get_required_envs().unwrap();
-        assert_eq!(api_keys.coingecko, "foo");
-        assert_eq!(api_keys.polygonio, "bar");
+    fn test_construct_coingecko_v3_markets_query_url_in_currency() {
+        let constructed_url =
+            construct_coingecko_v3_markets_query_url(COINGECKO_BASE_URL, "bitcoin", "CHF").unwrap();
+        assert_eq!(constructed_url.query(), Some("vs_currency=chf&ids=bitcoin"));
     }
 
     #[test]
-    fn test_get_required_envs_returns_error_if_not_set() {
-        env::remove_var("COINGECKO_KEY");
-        env::remove_var("POLYGON_KEY");
-        let result = get_required_envs();
-        match result {
-            Err(Error::EnvVarError(..)) => assert!(true),
-            _ => assert!(false, "Expected Error::EnvVarError"),
-        }
+    fn test_construct_tickerdetailsv3_query_url_with_custom_base_url() {
+        let base_urls = BaseUrls::new("http://localhost:8080/", COINGECKO_BASE_URL).unwrap();
+        let constructed_url =
+            construct_tickerdetailsv3_query_url(base_urls.polygon(), "AAPL", None).unwrap();
+        let target_url = Url::parse("http://localhost:8080/v3/reference/tickers/AAPL").unwrap();
+        assert_eq!(constructed_url, target_url);
+    }
+
+    #[test]
+    fn test_construct_tickerdetailsv3_query_url_with_date() {
+        let date = NaiveDate::from_ymd_opt(2021, 11, 10);
+        let constructed_url =
+            construct_tickerdetailsv3_query_url(POLYGONIO_BASE_URL, "AAPL", date).unwrap();
+        let target_url =
+            Url::parse("https://api.polygon.io/v3/reference/tickers/AAPL?date=2021-11-10").unwrap();
+        assert_eq!(constructed_url, target_url);
+    }
+
+    #[test]
+    fn test_construct_range_aggs_query_url() {
+        let constructed_url = construct_range_aggs_query_url(
+            POLYGONIO_BASE_URL,
+            "C:XAUUSD",
+            NaiveDate::from_ymd_opt(2021, 11, 3).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 11, 10).unwrap(),
+        )
+        .unwrap();
+        let target_url = Url::parse("https://api.polygon.io/v2/aggs/ticker/C:XAUUSD/range/1/day/2021-11-03/2021-11-10?sort=asc").unwrap();
+        assert_eq!(constructed_url, target_url);
+    }
+
+    #[test]
+    fn test_construct_coingecko_v3_history_query_url() {
+        let constructed_url = construct_coingecko_v3_history_query_url(
+            COINGECKO_BASE_URL,
+            "bitcoin",
+            NaiveDate::from_ymd_opt(2021, 11, 10).unwrap(),
+        )
+        .unwrap();
+        let target_url = Url::parse("https://api.coingecko.com/api/v3/coins/bitcoin/history?date=10-11-2021&localization=false").unwrap();
+        assert_eq!(constructed_url, target_url);
+    }
+
+    #[test]
+    fn test_construct_coingecko_v3_market_chart_range_query_url() {
+        let constructed_url = construct_coingecko_v3_market_chart_range_query_url(
+            COINGECKO_BASE_URL,
+            "bitcoin",
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        )
+        .unwrap();
+        let target_url = Url::parse("https://api.coingecko.com/api/v3/coins/bitcoin/market_chart/range?vs_currency=usd&from=1704067200&to=1706745599").unwrap();
+        assert_eq!(constructed_url, target_url);
+    }
+
+    #[test]
+    fn test_base_urls_rejects_invalid_url() {
+        let result = BaseUrls::new("localhost", COINGECKO_BASE_URL);
+        assert!(matches!(result, Err(Error::InvalidUrl(..))));
+    }
+
+    #[test]
+    fn test_missing_polygon_key_is_reported_when_needed() {
+        let apikeys = ApiKeys::new(None, None);
+        assert!(matches!(
+            apikeys.polygonio_authorization(),
+            Err(Error::EnvVarError(var)) if var == "POLYGON_KEY"
+        ));
+        assert!(matches!(apikeys.coingecko_header(), Ok(None)));
+    }
+
+    #[test]
+    fn test_api_keys_debug_hides_keys() {
+        let apikeys = ApiKeys::new(Some(String::from("myPolygonIOKey")), None);
+        let debug = format!("{apikeys:?}");
+        assert!(!debug.contains("myPolygonIOKey"));
+        assert!(debug.contains("polygonio: Some(\"********\")"));
+    }
+
+    #[test]
+    fn test_coingecko_header_depends_on_plan() {
+        let apikeys = ApiKeys::new(None, Some(String::from("myCoinGeckoKey")));
+        assert_eq!(apikeys.coingecko_plan(), CoingeckoPlan::Demo);
+        assert_eq!(
+            apikeys.coingecko_header().unwrap(),
+            Some(("x-cg-demo-api-key", "myCoinGeckoKey"))
+        );
+        let apikeys = apikeys.with_coingecko_plan(CoingeckoPlan::Pro);
+        assert_eq!(
+            apikeys.coingecko_header().unwrap(),
+            Some(("x-cg-pro-api-key", "myCoinGeckoKey"))
+        );
+        let apikeys = ApiKeys::new(None, None).with_coingecko_plan(CoingeckoPlan::Pro);
+        assert!(matches!(
+            apikeys.coingecko_header(),
+            Err(Error::EnvVarError(..))
+        ));
     }
 
     #[test]
@@ -456,6 +1143,96 @@ mod tests {
         assert_eq!(aggs_ticker_v2.results[0].c, 2559.15);
     }
 
+    #[test]
+    fn test_deserialize_aggs_ticker_v2_range() {
+        let data = r#"
+            {
+                "ticker": "C:XAUUSD",
+                "queryCount": 2,
+                "resultsCount": 2,
+                "adjusted": true,
+                "results": [
+                    {"v": 88301, "vw": 1822.6131, "o": 1824.38, "c": 1830.44, "h": 1833.65, "l": 1810.81, "t": 1636329600000, "n": 88301},
+                    {"v": 102573, "vw": 1839.7401, "o": 1829.96, "c": 1849.27, "h": 1868.33, "l": 1823.89, "t": 1636416000000, "n": 102573}
+                ],
+                "status": "OK",
+                "request_id": "0ac2a1c58f9c3a4bf4fe0a7e0a1d4a52"
+            }
+            "#;
+        let aggs_ticker_v2: AggsTickerV2 = serde_json::from_str(data).unwrap();
+        assert_eq!(aggs_ticker_v2.results.last().unwrap().c, 1849.27);
+    }
+
+    #[test]
+    fn test_deserialize_aggs_ticker_v2_range_without_results() {
+        let data = r#"{"ticker":"C:XAUUSD","queryCount":0,"resultsCount":0,"adjusted":true,"status":"OK","request_id":"1"}"#;
+        let aggs_ticker_v2: AggsTickerV2 = serde_json::from_str(data).unwrap();
+        assert!(aggs_ticker_v2.results.is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_coingecko_market_chart_v3() {
+        let data = r#"
+            {
+              "prices": [[1704067200000, 42261.04], [1704153600000, 44179.92]],
+              "market_caps": [[1704067200000, 827596236151.2], [1704153600000, 865939631688.7]],
+              "total_volumes": [[1704067200000, 14097645686.5], [1704153600000, 29232702487.6]]
+            }
+            "#;
+        let market_chart: CoingeckoMarketChartV3 = serde_json::from_str(data).unwrap();
+        assert_eq!(market_chart.market_caps[1], (1704153600000, 865939631688.7));
+    }
+
+    #[test]
+    fn test_deserialize_coingecko_history_v3() {
+        let data = r#"
+            {
+              "id": "bitcoin",
+              "symbol": "btc",
+              "name": "Bitcoin",
+              "image": {"thumb": "", "small": ""},
+              "market_data": {
+                "current_price": {"usd": 66953.3, "eur": 57845.1},
+                "market_cap": {"usd": 1263713011342.6, "eur": 1091795022330.4},
+                "total_volume": {"usd": 47643432289.2, "eur": 41162326812.5}
+              }
+            }
+            "#;
+        let history: CoingeckoHistoryV3 = serde_json::from_str(data).unwrap();
+        assert_eq!(
+            history.market_data.unwrap().market_cap.get("usd"),
+            Some(&1263713011342.6)
+        );
+    }
+
+    #[test]
+    fn test_coingecko_error_detects_history_limit() {
+        let data = r#"
+            {
+              "error": {
+                "status": {
+                  "timestamp": "2026-10-16T08:00:00.000+00:00",
+                  "error_code": 10012,
+                  "error_message": "Your request exceeds the allowed time range. Public API users are limited to querying historical data within the past 365 days."
+                }
+              }
+            }
+            "#;
+        assert!(matches!(
+            coingecko_error(data.to_string()),
+            Error::CoingeckoHistoryLimit
+        ));
+        let data = r#"{"status": {"error_code": 10012, "error_message": "..."}}"#;
+        assert!(matches!(
+            coingecko_error(data.to_string()),
+            Error::CoingeckoHistoryLimit
+        ));
+        assert!(matches!(
+            coingecko_error(String::from("[]")),
+            Error::CoingeckoApi(..)
+        ));
+    }
+
     #[test]
     fn test_deserialize_coingecko_markets_v3() {
         let data = r#"
@@ -1,75 +1,175 @@
-use anyhow::Result;
-use clap::Parser;
+use chrono::{NaiveDate, Utc};
+use clap::{Parser, Subcommand};
 use colored::*;
+use commands::alert::AlertArgs;
+use commands::config::ConfigArgs;
+use commands::history::HistoryArgs;
+use commands::search::SearchArgs;
 use numfmt::*;
+use ratio_gang_cli::compare::{rank, ratio_matrix, Ranked};
+use ratio_gang_cli::config::{parse_duration, Config, OutputFormat};
+use ratio_gang_cli::ratelimit::RateLimiter;
+use ratio_gang_cli::transport::Mode;
 use ratio_gang_cli::{
-    get_required_envs, return_cyrpto_market_cap, return_gold_market_cap, return_stock_market_cap,
-    ApiKeys, Error,
+    ApiKeys, Commodity, ExchangeRate, Mass, ProviderRegistry, Quote, QuoteOptions, Transport,
 };
-use reqwest::Client;
 use serde_json::json;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 use std::process;
-const BAR_LENGTH: usize = 40;
+use std::time::Duration;
 
+mod commands;
+#[cfg(test)]
+#[path = "testing/quote.rs"]
+mod testing;
+
+const BAR_LENGTH: usize = 40;
+const TABLE_BAR_LENGTH: usize = 20;
 
 #[derive(Parser)]
 #[command(version
-    , about=None, long_about = "Compare market caps between crypto, stock and gold by calculating their ratio\n- CLI returns percentages and market caps\n- Requires https://polygon.io and https://coingecko.com API Keys as environmental variables: POLYGON_KEY and COINGECKO_KEY")
+    , about=None, long_about = "Compare market caps between crypto, stocks and precious metals by calculating their ratio\n- CLI returns percentages and market caps\n- Stocks and precious metals require a https://polygon.io API key as environmental variable POLYGON_KEY or in the config file\n- A https://coingecko.com API key in COINGECKO_KEY is optional, without one the public API is used")
     ]
 struct Cli {
-    #[arg(default_value = "ethereum")]
-    asset_a: Option<String>,
-    #[arg(default_value = "bitcoin")]
-    asset_b: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(
+        num_args = 2..,
+        help = "Assets to compare, more than two assets are ranked by market cap [default: assets of the config file or ethereum bitcoin]"
+    )]
+    assets: Vec<String>,
     #[arg(
-        long = "above-ground",
-        default_value_t = 212582.0,
-        help = "Set the estimated above ground stock of gold in tonnes"
+        long,
+        alias = "above-ground",
+        global = true,
+        value_name = "MASS",
+        help = "Set the estimated above ground stock of gold, in tonnes or with a unit: t, oz (troy ounces) or kg"
+    )]
+    above_ground_gold: Option<Mass>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "MASS",
+        help = "Set the estimated above ground stock of silver, in tonnes or with a unit: t, oz (troy ounces) or kg"
     )]
-    above_ground: f64,
+    above_ground_silver: Option<Mass>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "MASS",
+        help = "Set the estimated above ground stock of platinum, in tonnes or with a unit: t, oz (troy ounces) or kg"
+    )]
+    above_ground_platinum: Option<Mass>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "MASS",
+        help = "Set the estimated above ground stock of palladium, in tonnes or with a unit: t, oz (troy ounces) or kg"
+    )]
+    above_ground_palladium: Option<Mass>,
     #[arg(
         short,
         long,
-        help = "Return 'denumerator-asset numerator-asset percentage'. E.g.  'AAPL Gold 17'"
+        help = "Return 'denumerator-asset numerator-asset percentage'. E.g.  'AAPL Gold 17'. For more than two assets return 'rank asset percentage-of-largest percentage-of-total' per asset"
     )]
     plain: bool,
-    #[arg(short, long, help = "Return json")]
+    #[arg(short, long, conflicts_with = "plain", help = "Return json")]
     json: bool,
+    #[arg(
+        short,
+        long,
+        help = "Return a table of the market cap ratios between every pair of assets, row asset divided by column asset"
+    )]
+    matrix: bool,
+    #[arg(
+        long,
+        requires = "matrix",
+        conflicts_with_all = ["plain", "json"],
+        help = "Return the ratio matrix as csv"
+    )]
+    csv: bool,
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        help = "Compare market caps as of a past date"
+    )]
+    date: Option<NaiveDate>,
+    #[arg(
+        long,
+        value_name = "CODE",
+        value_parser = parse_currency,
+        help = "Report market caps in this currency, e.g. EUR, CHF or JPY [default: USD]"
+    )]
+    currency: Option<String>,
+    #[arg(
+        long,
+        value_name = "INTERVAL",
+        value_parser = parse_duration,
+        conflicts_with_all = ["plain", "json", "matrix", "date"],
+        help = "Keep running and refresh every INTERVAL, e.g. 30s or 5m, showing the changes since the previous update. The interval is raised if needed to respect the API rate limits"
+    )]
+    watch: Option<Duration>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        global = true,
+        conflicts_with = "replay",
+        help = "Save the raw API responses to DIR"
+    )]
+    record: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        global = true,
+        help = "Serve API responses recorded with --record from DIR instead of sending requests"
+    )]
+    replay: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Neither read nor write the response cache"
+    )]
+    no_cache: bool,
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "no_cache",
+        help = "Ignore cached market data and fetch fresh market caps, the coin list stays cached"
+    )]
+    refresh: bool,
 }
 
-#[derive(Debug, PartialEq)]
-enum MarketCapType {
-    Gold,
-    Stock,
-    Crypto,
-    Unknown,
+#[derive(Subcommand)]
+enum Command {
+    /// Ratio between two assets for every point in a date range
+    History(HistoryArgs),
+    /// Show or create the config file
+    Config(ConfigArgs),
+    /// Notify when the ratio between two assets crosses a threshold
+    Alert(AlertArgs),
+    /// Find the identifiers of stocks, cryptocurrencies and commodities by name or symbol
+    Search(SearchArgs),
 }
 
-fn identify_market_cap_type(market_cap: &String) -> MarketCapType {
-    match market_cap.as_str() {
-        "gold" | "Gold" => MarketCapType::Gold,
-        s if s == s.to_uppercase() => MarketCapType::Stock,
-        s if s == s.to_lowercase() => MarketCapType::Crypto,
-        _ => MarketCapType::Unknown,
+/// ISO 4217 currency code, case insensitive
+fn parse_currency(value: &str) -> std::result::Result<String, String> {
+    if value.len() == 3 && value.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(value.to_uppercase())
+    } else {
+        Err(format!(
+            "invalid currency '{value}', use a three letter code like EUR"
+        ))
     }
 }
 
-async fn return_market_cap(
-    client: &Client,
-    asset_name: &String,
-    apikeys: &ApiKeys,
-    above_ground: &f64,
-) -> Result<f64> {
-    match identify_market_cap_type(asset_name) {
-        MarketCapType::Gold => return_gold_market_cap(client, above_ground, apikeys).await,
-        MarketCapType::Stock => return_stock_market_cap(client, asset_name, apikeys).await,
-        MarketCapType::Crypto => return_cyrpto_market_cap(client, asset_name, apikeys).await,
-        MarketCapType::Unknown => Err(Error::UnknownAssetName(asset_name.clone()).into()),
-    }
+/// Rate used to convert any of the quotes from USD
+fn exchange_rate<'a>(quotes: impl IntoIterator<Item = &'a Quote>) -> Option<&'a ExchangeRate> {
+    quotes.into_iter().find_map(|q| q.exchange_rate.as_ref())
 }
 
 fn create_ratio_gauge(ratio: f64, total_length: usize) -> String {
-    if ratio < 0.0 || ratio > 1.0 {
+    if !(0.0..=1.0).contains(&ratio) {
         panic!("Ratio must be between 0 and 1");
     }
     let filled_length = (ratio * total_length as f64).round() as usize;
@@ -80,120 +180,344 @@ fn create_ratio_gauge(ratio: f64, total_length: usize) -> String {
     format!("[{}{}] {}%", filled_part, empty_part, percentage)
 }
 
+/// Ratio of two market caps, unset when it is undefined, e.g. when both are 0
+fn market_cap_ratio(numerator: f64, denominator: f64) -> Option<f64> {
+    Some(numerator / denominator).filter(|ratio| ratio.is_finite())
+}
+
+/// Gauge of a ratio, "n/a" when it is undefined
+fn ratio_gauge(ratio: Option<f64>, total_length: usize) -> String {
+    match ratio {
+        Some(ratio) => create_ratio_gauge(ratio, total_length),
+        None => String::from("n/a"),
+    }
+}
+
+fn market_cap_formatter() -> Formatter {
+    Formatter::default()
+        .scales(Scales::short())
+        .precision(Precision::Decimals(1))
+}
+
+/// Ratio between the smaller and the larger market cap of two assets
+fn print_ratio(cli: &Cli, a: &Quote, b: &Quote) {
+    let mut f = market_cap_formatter();
+    let (numerator, denominator) = if a.market_cap < b.market_cap {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    let ratio = market_cap_ratio(numerator.market_cap, denominator.market_cap);
+    let percentage = ratio.map(|ratio| (ratio * 100.0) as u32);
+    if cli.plain {
+        let percentage = percentage.map_or_else(|| String::from("n/a"), |p| p.to_string());
+        println!("{} {} {}", numerator.asset, denominator.asset, percentage)
+    } else if cli.json {
+        let json = json!({
+            "date": cli.date,
+            "currency": numerator.currency,
+            "exchange_rate": exchange_rate([a, b]),
+            "percentage": percentage,
+            "numerator": {
+                "asset": numerator.asset,
+                "market_cap": numerator.market_cap as u64
+            },
+            "denominator": {
+                "asset": denominator.asset,
+                "market_cap": denominator.market_cap as u64
+            },
+        });
+        println!("{}", json);
+    } else {
+        println!("{}", ratio_gauge(ratio, BAR_LENGTH));
+        println!("{}: {}", numerator.asset, f.fmt2(numerator.market_cap));
+        println!("{}: {}", denominator.asset, f.fmt2(denominator.market_cap));
+    }
+}
+
+/// Table of assets ranked by market cap with their share of the largest and of the total
+fn print_ranking(cli: &Cli, ranking: &[Ranked]) {
+    let mut f = market_cap_formatter();
+    if cli.plain {
+        for r in ranking {
+            println!(
+                "{} {} {} {}",
+                r.rank,
+                r.quote.asset,
+                (r.share_of_largest * 100.0) as u32,
+                (r.share_of_total * 100.0) as u32
+            )
+        }
+    } else if cli.json {
+        let total: f64 = ranking.iter().map(|r| r.quote.market_cap).sum();
+        let assets: Vec<_> = ranking
+            .iter()
+            .map(|r| {
+                json!({
+                    "rank": r.rank,
+                    "asset": r.quote.asset,
+                    "market_cap": r.quote.market_cap as u64,
+                    "share_of_largest": r.share_of_largest,
+                    "share_of_total": r.share_of_total,
+                })
+            })
+            .collect();
+        let json = json!({
+            "date": cli.date,
+            "currency": ranking.first().map(|r| r.quote.currency.as_str()),
+            "exchange_rate": exchange_rate(ranking.iter().map(|r| &r.quote)),
+            "total_market_cap": total as u64,
+            "assets": assets,
+        });
+        println!("{}", json);
+    } else {
+        let width = ranking
+            .iter()
+            .map(|r| r.quote.asset.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max("asset".len());
+        println!(
+            "{:>2}  {:<width$}  {:>10}  {:>5}  of largest",
+            "#", "asset", "market cap", "total"
+        );
+        for r in ranking {
+            println!(
+                "{:>2}  {:<width$}  {:>10}  {:>4}%  {}",
+                r.rank,
+                r.quote.asset,
+                f.fmt2(r.quote.market_cap),
+                (r.share_of_total * 100.0).round(),
+                create_ratio_gauge(r.share_of_largest, TABLE_BAR_LENGTH)
+            );
+        }
+    }
+}
+
+/// Ratio as percentage, with one decimal below 10%
+fn format_percentage(ratio: f64) -> String {
+    let percentage = ratio * 100.0;
+    if percentage < 10.0 {
+        format!("{:.1}%", percentage)
+    } else {
+        format!("{:.0}%", percentage)
+    }
+}
+
+/// Pairwise ratios between all assets, cells where the row asset is the smaller one are green.
+/// Ratios to a market cap of 0 are "n/a", empty in csv and null in json.
+fn print_matrix(cli: &Cli, quotes: &[Quote]) {
+    let matrix = ratio_matrix(quotes);
+    let assets: Vec<&str> = quotes.iter().map(|q| q.asset.as_str()).collect();
+    if cli.csv {
+        println!(",{}", assets.join(","));
+        for (asset, row) in assets.iter().zip(&matrix) {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|ratio| ratio.map(|r| r.to_string()).unwrap_or_default())
+                .collect();
+            println!("{},{}", asset, cells.join(","));
+        }
+    } else if cli.plain {
+        for (row_asset, row) in assets.iter().zip(&matrix) {
+            for (column_asset, ratio) in assets.iter().zip(row) {
+                let percentage = ratio.map_or_else(
+                    || String::from("n/a"),
+                    |ratio| ((ratio * 100.0) as u64).to_string(),
+                );
+                println!("{} {} {}", row_asset, column_asset, percentage);
+            }
+        }
+    } else if cli.json {
+        let json = json!({
+            "date": cli.date,
+            "assets": assets,
+            "ratios": matrix,
+        });
+        println!("{}", json);
+    } else {
+        let cells: Vec<Vec<String>> = matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|ratio| ratio.map_or_else(|| String::from("n/a"), format_percentage))
+                    .collect()
+            })
+            .collect();
+        let width = assets
+            .iter()
+            .map(|a| a.chars().count())
+            .chain(cells.iter().flatten().map(|c| c.len()))
+            .max()
+            .unwrap_or_default();
+        let header: Vec<String> = assets.iter().map(|a| format!("{:>width$}", a)).collect();
+        println!("{:width$}  {}", "", header.join("  "));
+        for (i, (asset, row)) in assets.iter().zip(&cells).enumerate() {
+            let row: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(j, cell)| {
+                    let cell = format!("{:>width$}", cell);
+                    if i == j {
+                        cell.dimmed().to_string()
+                    } else if matrix[i][j].is_some_and(|ratio| ratio < 1.0) {
+                        cell.green().to_string()
+                    } else {
+                        cell
+                    }
+                })
+                .collect();
+            println!("{:<width$}  {}", asset, row.join("  "));
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    let mut f = Formatter::default()
-        .scales(Scales::short())
-        .precision(Precision::Decimals(1));
-    let client = reqwest::Client::new();
-    let cli = Cli::parse();
-    let apikeys = match get_required_envs() {
+    let mut cli = Cli::parse();
+    // before loading the config, so that a broken config file can be replaced
+    if let Some(Command::Config(args)) = &cli.command {
+        return commands::config::run(args);
+    }
+    let config = match Config::load() {
         Ok(value) => value,
         Err(error) => {
-            eprintln!("{error}");
+            eprint!("{error}");
             process::exit(1)
         }
     };
-    let above_ground = cli.above_ground;
-    let asset_a = match cli.asset_a.as_ref() {
-        Some(asset_a) => asset_a,
-        None => {
-            eprintln!("Missing left hand asset, see --help for usage");
+    // command line arguments take precedence over the config file
+    if cli.assets.is_empty() {
+        cli.assets = config.assets();
+    }
+    if !(cli.plain || cli.json) {
+        match config.output() {
+            OutputFormat::Human => {}
+            OutputFormat::Plain => cli.plain = true,
+            OutputFormat::Json => cli.json = true,
+        }
+    }
+    let mode = match (cli.record.clone(), cli.replay.clone()) {
+        (Some(dir), _) => Mode::Record(dir),
+        (_, Some(dir)) => Mode::Replay(dir),
+        _ => Mode::Live,
+    };
+    let apikeys = match mode {
+        // replayed responses don't need api keys, fixture names never contain them
+        Mode::Replay(_) => ApiKeys::new(Some(String::new()), None),
+        _ => config.api_keys(),
+    };
+    let base_urls = match config.base_urls() {
+        Ok(value) => value,
+        Err(error) => {
+            eprint!("{error}");
             process::exit(1)
         }
     };
-    let asset_b = match cli.asset_b.as_ref() {
-        Some(asset_b) => asset_b,
-        None => {
-            eprintln!("Missing right hand asset, see --help for usage");
+    let cache = match config.cache() {
+        Ok(value) => value,
+        Err(error) => {
+            eprint!("{error}");
             process::exit(1)
         }
     };
-    let left_hand_market_cap =
-        match return_market_cap(&client, &asset_a, &apikeys, &above_ground).await {
-            Ok(market_cap) => market_cap,
-            Err(error) => {
-                eprint!("{error}");
-                process::exit(1)
-            }
-        };
-    let right_hand_market_cap =
-        match return_market_cap(&client, &asset_b, &apikeys, &above_ground).await {
-            Ok(market_cap) => market_cap,
-            Err(error) => {
-                eprint!("{error}");
-                process::exit(1)
+    let retry = match config.retry() {
+        Ok(value) => value,
+        Err(error) => {
+            eprint!("{error}");
+            process::exit(1)
+        }
+    };
+    let mut above_ground = match config.above_ground() {
+        Ok(value) => value,
+        Err(error) => {
+            eprint!("{error}");
+            process::exit(1)
+        }
+    };
+    for (commodity, mass) in [
+        (Commodity::Gold, cli.above_ground_gold),
+        (Commodity::Silver, cli.above_ground_silver),
+        (Commodity::Platinum, cli.above_ground_platinum),
+        (Commodity::Palladium, cli.above_ground_palladium),
+    ] {
+        if let Some(mass) = mass {
+            above_ground = above_ground.with(commodity, mass);
+        }
+    }
+    let registry = ProviderRegistry::with_defaults(&base_urls, &apikeys, above_ground)
+        .with_aliases(config.aliases.clone())
+        .with_rate_limits(&config.rate_limits());
+    let mut limiter = RateLimiter::new(&registry.rate_limits());
+    if io::stderr().is_terminal() {
+        limiter = limiter.with_observer(commands::show_queue);
+    }
+    let mut transport = Transport::new(reqwest::Client::new(), mode)
+        .with_retry(retry)
+        .with_rate_limiter(limiter);
+    if let Some(cache) = cache.filter(|_| !cli.no_cache) {
+        // watching and alerting always fetch fresh market caps, the coin list stays cached
+        let polling = cli.watch.is_some() || matches!(cli.command, Some(Command::Alert(_)));
+        transport = transport.with_cache(cache.with_refresh(cli.refresh || polling));
+    }
+    match &cli.command {
+        Some(Command::History(args)) => commands::history::run(args, &registry, &transport).await,
+        Some(Command::Alert(args)) => commands::alert::run(args, &registry, &transport).await,
+        Some(Command::Search(args)) => {
+            commands::search::run(args, &base_urls, &apikeys, &transport).await
+        }
+        Some(Command::Config(_)) => unreachable!("handled before loading the config"),
+        None => match cli.watch {
+            Some(interval) => {
+                let options = QuoteOptions {
+                    currency: cli.currency.clone(),
+                    ..Default::default()
+                };
+                commands::watch::run(&cli.assets, interval, &options, &registry, &transport).await
             }
-        };
-
-    let (ratio, numerator_asset, denominator_asset, numerator_value, denominator_value) =
-        if left_hand_market_cap < right_hand_market_cap {
-            (
-                left_hand_market_cap / right_hand_market_cap,
-                asset_a,
-                asset_b,
-                left_hand_market_cap,
-                right_hand_market_cap,
-            )
-        } else {
-            (
-                right_hand_market_cap / left_hand_market_cap,
-                asset_b,
-                asset_a,
-                right_hand_market_cap,
-                left_hand_market_cap,
-            )
-        };
-    let percentage: u32 = (ratio * 100.0) as u32;
-    if cli.plain {
-        println!("{} {} {}", numerator_asset, denominator_asset, percentage)
-    } else if cli.json {
-        let json = json!({
-            "percentage": percentage,
-            "numerator": {
-                "asset": numerator_asset,
-                "market_cap": numerator_value as u64
-            },
-            "denominator": {
-                "asset": denominator_asset,
-                "market_cap": denominator_value as u64
-            },
-        });
-        println!("{}", json.to_string());
-    } else {
-        println!("{}", create_ratio_gauge(ratio, BAR_LENGTH));
-        println!("{}: {}", numerator_asset, f.fmt2(numerator_value));
-        println!("{}: {}", denominator_asset, f.fmt2(denominator_value));
+            None => compare(&cli, &registry, &transport).await,
+        },
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_identify_market_cap_type_for_gold() {
-        let m = String::from("gold");
-        assert_eq!(MarketCapType::Gold, identify_market_cap_type(&m))
-    }
-    #[test]
-    fn test_identify_market_cap_type_for_capital_gold() {
-        let m = String::from("Gold");
-        assert_eq!(MarketCapType::Gold, identify_market_cap_type(&m))
-    }
-    #[test]
-    fn test_identify_market_cap_type_for_crypto() {
-        let m = String::from("ethereum");
-        assert_eq!(MarketCapType::Crypto, identify_market_cap_type(&m))
-    }
-    #[test]
-    fn test_identify_market_cap_type_for_stock() {
-        let m = String::from("AAPL");
-        assert_eq!(MarketCapType::Stock, identify_market_cap_type(&m))
-    }
-    #[test]
-    fn test_identify_market_cap_type_for_unknown() {
-        let m = String::from("FooBar");
-        assert_eq!(MarketCapType::Unknown, identify_market_cap_type(&m))
+async fn compare(cli: &Cli, registry: &ProviderRegistry, transport: &Transport) {
+    if cli.date.is_some_and(|date| date > Utc::now().date_naive()) {
+        eprintln!("--date must not be in the future");
+        process::exit(1)
+    }
+    let options = QuoteOptions {
+        date: cli.date,
+        currency: cli.currency.clone(),
+    };
+    let quotes = match registry.fetch_all(transport, &cli.assets, &options).await {
+        Ok(quotes) => quotes,
+        Err(error) => {
+            eprint!("{error}");
+            process::exit(1)
+        }
+    };
+    for note in quotes.iter().filter_map(|quote| quote.note.as_ref()) {
+        eprintln!("note: {note}");
+    }
+    let human = !(cli.plain || cli.json || cli.csv);
+    if let Some(date) = cli.date.filter(|_| human) {
+        println!("as of {date}");
+    }
+    if let Some(currency) = cli.currency.as_ref().filter(|_| human && !cli.matrix) {
+        match exchange_rate(&quotes) {
+            Some(rate) => println!(
+                "in {currency}, 1 USD = {:.4} {currency} as of {}",
+                rate.rate,
+                rate.time.format("%Y-%m-%d %H:%M UTC")
+            ),
+            None => println!("in {currency}"),
+        }
+    }
+    if cli.matrix {
+        print_matrix(cli, &quotes);
+    } else if let [a, b] = quotes.as_slice() {
+        print_ratio(cli, a, b);
+    } else {
+        print_ranking(cli, &rank(&quotes));
     }
 }
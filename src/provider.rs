@@ -0,0 +1,1574 @@
+use crate::coins::CoinId;
+use crate::transport::Source;
+use crate::{
+    coins, return_commodity_market_cap, return_commodity_market_cap_history,
+    return_crypto_market_cap_history, return_crypto_market_caps, return_exchange_rate,
+    return_historical_crypto_market_cap, return_stock_market_cap, return_stock_market_cap_history,
+    AboveGround, ApiKeys, BaseUrls, Commodity, Error, ExchangeRate, Series, Transport,
+    COINGECKO_MARKETS_PAGE_SIZE,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use futures::future::{join_all, try_join_all};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Currency of the market caps reported by polygon.io
+const USD: &str = "USD";
+
+/// Quota of polygon.io, shared by stocks, commodities and exchange rates
+pub const POLYGON_API: &str = "polygon.io";
+/// polygon.io free plan
+pub const POLYGON_RATE_LIMIT: RateLimit = RateLimit {
+    api: POLYGON_API,
+    requests_per_minute: 5,
+};
+/// Quota of coingecko.com, the requests per minute depend on the plan
+pub const COINGECKO_API: &str = "coingecko.com";
+
+/// Stock market caps from polygon.io
+pub const POLYGON: Source = Source {
+    name: "polygon",
+    api: POLYGON_API,
+};
+/// Commodity prices from polygon.io
+pub const COMMODITY: Source = Source {
+    name: "commodity",
+    api: POLYGON_API,
+};
+/// Exchange rates from polygon.io
+pub const FOREX: Source = Source {
+    name: "forex",
+    api: POLYGON_API,
+};
+/// Crypto market caps from coingecko
+pub const COINGECKO: Source = Source {
+    name: "coingecko",
+    api: COINGECKO_API,
+};
+/// The coingecko coin list, to resolve symbols and names
+pub const COINS: Source = Source {
+    name: "coins",
+    api: COINGECKO_API,
+};
+/// Sources of the built-in providers, the keys of `[cache.ttl]` and `[retry.*]` in the config
+pub const SOURCES: [Source; 5] = [POLYGON, COMMODITY, FOREX, COINGECKO, COINS];
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarketCapType {
+    Commodity,
+    Stock,
+    Crypto,
+    Unknown,
+}
+
+impl fmt::Display for MarketCapType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MarketCapType::Commodity => "commodity",
+            MarketCapType::Stock => "stock",
+            MarketCapType::Crypto => "crypto",
+            MarketCapType::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+const PREFIXES: [(&str, MarketCapType); 3] = [
+    ("stock:", MarketCapType::Stock),
+    ("crypto:", MarketCapType::Crypto),
+    ("commodity:", MarketCapType::Commodity),
+];
+
+/// Splits an explicit `stock:`, `crypto:` or `commodity:` prefix off `asset`
+fn split_prefix(asset: &str) -> Option<(MarketCapType, &str)> {
+    PREFIXES.iter().find_map(|(prefix, market_cap_type)| {
+        asset
+            .strip_prefix(prefix)
+            .map(|name| (*market_cap_type, name))
+    })
+}
+
+/// Type given by an explicit prefix, otherwise guessed from the letter case: all caps are stock
+/// symbols and lower case are coingecko ids
+pub fn identify_market_cap_type(market_cap: &str) -> MarketCapType {
+    if let Some((market_cap_type, _)) = split_prefix(market_cap) {
+        return market_cap_type;
+    }
+    match market_cap {
+        s if Commodity::from_name(s).is_some() => MarketCapType::Commodity,
+        s if s == s.to_uppercase() => MarketCapType::Stock,
+        s if s == s.to_lowercase() => MarketCapType::Crypto,
+        _ => MarketCapType::Unknown,
+    }
+}
+
+/// Types to try for `asset` in order, with the name to look the asset up by. An explicit prefix
+/// yields only that type. Otherwise the guess from the letter case comes first, followed by
+/// stock or crypto, so that e.g. a crypto symbol like BTC is still found. Crypto names keep their
+/// case, coingecko resolves symbols and names that aren't lower case ids.
+pub fn classify(asset: &str) -> Vec<(MarketCapType, String)> {
+    if let Some((market_cap_type, name)) = split_prefix(asset) {
+        let name = match market_cap_type {
+            MarketCapType::Commodity => name.to_lowercase(),
+            _ => name.to_string(),
+        };
+        return vec![(market_cap_type, name)];
+    }
+    let crypto = (MarketCapType::Crypto, asset.to_string());
+    let stock = (MarketCapType::Stock, asset.to_uppercase());
+    match identify_market_cap_type(asset) {
+        MarketCapType::Commodity => vec![(MarketCapType::Commodity, asset.to_string())],
+        MarketCapType::Stock => vec![stock, crypto],
+        MarketCapType::Crypto | MarketCapType::Unknown => vec![crypto, stock],
+    }
+}
+
+/// Market cap of a single asset as reported by a provider
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Quote {
+    pub asset: String,
+    pub market_cap_type: MarketCapType,
+    pub market_cap: f64,
+    /// ISO 4217 code of the currency of `market_cap`
+    pub currency: String,
+    /// Rate the market cap was converted from USD with, unset if the provider reported it in
+    /// `currency`
+    pub exchange_rate: Option<ExchangeRate>,
+    pub provider: &'static str,
+    /// How an ambiguous asset was resolved, e.g. which of the coins sharing a symbol was used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Request quota of an API, providers querying the same API share it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub api: &'static str,
+    pub requests_per_minute: u32,
+}
+
+/// Parameters shared by all assets of a lookup
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuoteOptions {
+    /// Market cap as of this date instead of the latest available
+    pub date: Option<NaiveDate>,
+    /// ISO 4217 code of the currency of the market caps, USD if unset
+    pub currency: Option<String>,
+}
+
+impl QuoteOptions {
+    pub fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or(USD)
+    }
+}
+
+#[async_trait]
+pub trait MarketCapProvider: Send + Sync {
+    /// Short name used in output and error messages
+    fn name(&self) -> &'static str;
+    /// The kind of asset this provider is responsible for
+    fn market_cap_type(&self) -> MarketCapType;
+    /// Identifies the upstream endpoint queried for `asset`, assets with the same endpoint
+    /// are only fetched once per lookup
+    fn endpoint(&self, asset: &str) -> String {
+        format!("{}:{asset}", self.name())
+    }
+    /// Quota of the API queried by the provider, see [`MarketCapProvider::requests`] for what a
+    /// lookup costs
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+    /// Requests sent by a lookup of the latest market caps of `assets`, one per endpoint unless
+    /// the provider fetches them in batches
+    fn requests(&self, assets: &[String]) -> usize {
+        let mut endpoints: Vec<String> = assets.iter().map(|a| self.endpoint(a)).collect();
+        endpoints.sort();
+        endpoints.dedup();
+        endpoints.len()
+    }
+    async fn fetch(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        options: &QuoteOptions,
+    ) -> Result<Quote>;
+    /// Quotes of `assets` in their order. Providers whose API can look up many assets at once
+    /// override this to save requests, the outer error is a failure of such a batched lookup.
+    async fn fetch_many(
+        &self,
+        transport: &Transport,
+        assets: &[String],
+        options: &QuoteOptions,
+    ) -> Result<Vec<Result<Quote>>> {
+        Ok(join_all(
+            assets
+                .iter()
+                .map(|asset| self.fetch(transport, asset, options)),
+        )
+        .await)
+    }
+    /// Daily market caps between `from` and `to`, providers without history return an error
+    async fn history(
+        &self,
+        _transport: &Transport,
+        asset: &str,
+        _from: NaiveDate,
+        _to: NaiveDate,
+    ) -> Result<Series> {
+        Err(Error::HistoryUnsupported(asset.to_string()).into())
+    }
+}
+
+/// Stocks via polygon.io ticker details
+pub struct PolygonProvider {
+    base_urls: BaseUrls,
+    apikeys: ApiKeys,
+}
+
+impl PolygonProvider {
+    pub fn new(base_urls: BaseUrls, apikeys: ApiKeys) -> Self {
+        Self { base_urls, apikeys }
+    }
+}
+
+#[async_trait]
+impl MarketCapProvider for PolygonProvider {
+    fn name(&self) -> &'static str {
+        POLYGON.name
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        Some(POLYGON_RATE_LIMIT)
+    }
+
+    fn market_cap_type(&self) -> MarketCapType {
+        MarketCapType::Stock
+    }
+
+    async fn fetch(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        let market_cap = return_stock_market_cap(
+            transport,
+            &self.base_urls,
+            asset,
+            options.date,
+            &self.apikeys,
+        )
+        .await?;
+        Ok(Quote {
+            asset: asset.to_string(),
+            market_cap_type: self.market_cap_type(),
+            market_cap,
+            currency: String::from(USD),
+            exchange_rate: None,
+            provider: self.name(),
+            note: None,
+        })
+    }
+
+    async fn history(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Series> {
+        return_stock_market_cap_history(transport, &self.base_urls, asset, from, to, &self.apikeys)
+            .await
+    }
+}
+
+/// Cryptocurrencies via coingecko.com markets
+pub struct CoinGeckoProvider {
+    base_urls: BaseUrls,
+    apikeys: ApiKeys,
+}
+
+impl CoinGeckoProvider {
+    pub fn new(base_urls: BaseUrls, apikeys: ApiKeys) -> Self {
+        Self { base_urls, apikeys }
+    }
+}
+
+impl CoinGeckoProvider {
+    /// Quote of `asset` resolved to `coin`, with the market cap from `latest` or of the date of
+    /// `options`
+    async fn quote(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        coin: Result<CoinId>,
+        latest: &HashMap<String, f64>,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        let coin = coin?;
+        let market_cap = match options.date {
+            Some(date) => {
+                return_historical_crypto_market_cap(
+                    transport,
+                    &self.base_urls,
+                    &coin.id,
+                    date,
+                    options.currency(),
+                    &self.apikeys,
+                )
+                .await?
+            }
+            None => *latest
+                .get(&coin.id)
+                .ok_or_else(|| Error::NoData(coin.id.clone()))?,
+        };
+        Ok(Quote {
+            asset: asset.to_string(),
+            market_cap_type: self.market_cap_type(),
+            market_cap,
+            currency: options.currency().to_string(),
+            exchange_rate: None,
+            provider: self.name(),
+            note: coin.note,
+        })
+    }
+}
+
+#[async_trait]
+impl MarketCapProvider for CoinGeckoProvider {
+    fn name(&self) -> &'static str {
+        COINGECKO.name
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        Some(RateLimit {
+            api: COINGECKO_API,
+            requests_per_minute: self.apikeys.coingecko_plan().requests_per_minute(),
+        })
+    }
+
+    /// The latest market caps of up to a page of coins share one `/coins/markets` request
+    fn requests(&self, assets: &[String]) -> usize {
+        let mut ids = assets.to_vec();
+        ids.sort();
+        ids.dedup();
+        ids.len().div_ceil(COINGECKO_MARKETS_PAGE_SIZE)
+    }
+
+    fn market_cap_type(&self) -> MarketCapType {
+        MarketCapType::Crypto
+    }
+
+    async fn fetch(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        let mut quotes = self
+            .fetch_many(transport, &[asset.to_string()], options)
+            .await?;
+        quotes.remove(0)
+    }
+
+    /// Latest market caps of all assets with one coin list and one markets request, past market
+    /// caps are looked up per asset. Coingecko reports market caps in many currencies, no conversion needed.
+    async fn fetch_many(
+        &self,
+        transport: &Transport,
+        assets: &[String],
+        options: &QuoteOptions,
+    ) -> Result<Vec<Result<Quote>>> {
+        let coins = coins::resolve_all(transport, &self.base_urls, assets, &self.apikeys).await?;
+        let latest = match options.date {
+            Some(_) => HashMap::new(),
+            None => {
+                let ids: Vec<&str> = coins
+                    .iter()
+                    .filter_map(|coin| Some(coin.as_ref().ok()?.id.as_str()))
+                    .collect();
+                return_crypto_market_caps(
+                    transport,
+                    &self.base_urls,
+                    &ids,
+                    options.currency(),
+                    &self.apikeys,
+                )
+                .await?
+            }
+        };
+        Ok(join_all(
+            assets
+                .iter()
+                .zip(coins)
+                .map(|(asset, coin)| self.quote(transport, asset, coin, &latest, options)),
+        )
+        .await)
+    }
+
+    async fn history(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Series> {
+        return_crypto_market_cap_history(transport, &self.base_urls, asset, from, to, &self.apikeys)
+            .await
+    }
+}
+
+/// Precious metals valued as the previous day's close of their forex pair times the above
+/// ground stock
+pub struct CommodityProvider {
+    base_urls: BaseUrls,
+    apikeys: ApiKeys,
+    above_ground: AboveGround,
+}
+
+impl CommodityProvider {
+    pub fn new(base_urls: BaseUrls, apikeys: ApiKeys, above_ground: AboveGround) -> Self {
+        Self {
+            base_urls,
+            apikeys,
+            above_ground,
+        }
+    }
+}
+
+fn commodity(asset: &str) -> Result<Commodity> {
+    Commodity::from_name(asset).ok_or_else(|| Error::UnknownAssetName(asset.to_string()).into())
+}
+
+#[async_trait]
+impl MarketCapProvider for CommodityProvider {
+    fn name(&self) -> &'static str {
+        COMMODITY.name
+    }
+
+    fn market_cap_type(&self) -> MarketCapType {
+        MarketCapType::Commodity
+    }
+
+    fn endpoint(&self, asset: &str) -> String {
+        match Commodity::from_name(asset) {
+            Some(commodity) => format!("{}:{}", self.name(), commodity.ticker()),
+            None => format!("{}:{asset}", self.name()),
+        }
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        Some(POLYGON_RATE_LIMIT)
+    }
+
+    async fn fetch(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        let commodity = commodity(asset)?;
+        let market_cap = return_commodity_market_cap(
+            transport,
+            &self.base_urls,
+            commodity,
+            self.above_ground.get(commodity),
+            options.date,
+            &self.apikeys,
+        )
+        .await?;
+        Ok(Quote {
+            asset: asset.to_string(),
+            market_cap_type: self.market_cap_type(),
+            market_cap,
+            currency: String::from(USD),
+            exchange_rate: None,
+            provider: self.name(),
+            note: None,
+        })
+    }
+
+    async fn history(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Series> {
+        let commodity = commodity(asset)?;
+        return_commodity_market_cap_history(
+            transport,
+            &self.base_urls,
+            commodity,
+            self.above_ground.get(commodity),
+            from,
+            to,
+            &self.apikeys,
+        )
+        .await
+    }
+}
+
+/// One failed classification of an asset, e.g. `stock BITCOIN (Ticker not found.)`
+fn attempt(market_cap_type: MarketCapType, name: &str, error: anyhow::Error) -> String {
+    let error = error.to_string();
+    let error = error.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+    format!("{market_cap_type} {name} ({error})")
+}
+
+/// Whether a failed classification ends the lookup. Only an asset the API doesn't know is tried
+/// as another type, a missing key or a rate limit would otherwise report a different asset of
+/// the same name.
+fn is_final(error: &anyhow::Error) -> bool {
+    !error
+        .downcast_ref::<Error>()
+        .is_some_and(Error::is_not_found)
+}
+
+fn not_found(asset: &str, tried: Vec<String>) -> anyhow::Error {
+    if tried.is_empty() {
+        Error::UnknownAssetName(asset.to_string()).into()
+    } else {
+        Error::AssetNotFound(asset.to_string(), tried.join(", ")).into()
+    }
+}
+
+/// Maps each market cap type to the provider responsible for it.
+/// Registering a provider for a type that is already covered replaces the previous one.
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: HashMap<MarketCapType, Box<dyn MarketCapProvider>>,
+    /// polygon.io access for converting USD market caps to other currencies
+    forex: Option<(BaseUrls, ApiKeys)>,
+    /// Alternative names of assets, e.g. `btc` for `bitcoin`
+    aliases: HashMap<String, String>,
+    /// Requests per minute per API overriding the quotas of the providers, e.g. of a paid plan
+    rate_limits: HashMap<&'static str, u32>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the polygon, coingecko and commodity providers
+    pub fn with_defaults(
+        base_urls: &BaseUrls,
+        apikeys: &ApiKeys,
+        above_ground: AboveGround,
+    ) -> Self {
+        let mut registry = Self::new();
+        registry.forex = Some((base_urls.clone(), apikeys.clone()));
+        registry.register(PolygonProvider::new(base_urls.clone(), apikeys.clone()));
+        registry.register(CoinGeckoProvider::new(base_urls.clone(), apikeys.clone()));
+        registry.register(CommodityProvider::new(
+            base_urls.clone(),
+            apikeys.clone(),
+            above_ground,
+        ));
+        registry
+    }
+
+    pub fn with_rate_limits(mut self, limits: &[RateLimit]) -> Self {
+        for limit in limits {
+            self.rate_limits
+                .insert(limit.api, limit.requests_per_minute);
+        }
+        self
+    }
+
+    /// `limit` with the configured override
+    fn quota(&self, limit: RateLimit) -> RateLimit {
+        RateLimit {
+            requests_per_minute: self
+                .rate_limits
+                .get(limit.api)
+                .copied()
+                .unwrap_or(limit.requests_per_minute),
+            ..limit
+        }
+    }
+
+    /// Quota of the API queried by `provider`, with the configured override
+    fn rate_limit(&self, provider: &dyn MarketCapProvider) -> Option<RateLimit> {
+        provider.rate_limit().map(|limit| self.quota(limit))
+    }
+
+    /// Quota of the exchange rate lookups, unset without a source of exchange rates
+    fn forex_rate_limit(&self) -> Option<RateLimit> {
+        self.forex.as_ref().map(|_| self.quota(POLYGON_RATE_LIMIT))
+    }
+
+    /// Quotas of the APIs queried by the registered providers and exchange rate lookups, ordered
+    /// by API
+    pub fn rate_limits(&self) -> Vec<RateLimit> {
+        let mut limits: Vec<RateLimit> = self
+            .providers
+            .values()
+            .filter_map(|provider| self.rate_limit(provider.as_ref()))
+            .chain(self.forex_rate_limit())
+            .collect();
+        limits.sort_by_key(|limit| limit.api);
+        limits.dedup_by_key(|limit| limit.api);
+        limits
+    }
+
+    pub fn register(&mut self, provider: impl MarketCapProvider + 'static) {
+        self.providers
+            .insert(provider.market_cap_type(), Box::new(provider));
+    }
+
+    pub fn provider_for(&self, market_cap_type: MarketCapType) -> Option<&dyn MarketCapProvider> {
+        self.providers.get(&market_cap_type).map(|p| p.as_ref())
+    }
+
+    /// Looks up assets given by an alias under the asset it stands for. Quotes keep the alias.
+    pub fn with_aliases(mut self, aliases: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.aliases.extend(aliases);
+        self
+    }
+
+    /// The asset `asset` is an alias of, or `asset` itself
+    pub fn resolve<'a>(&'a self, asset: &'a str) -> &'a str {
+        self.aliases.get(asset).map_or(asset, |a| a.as_str())
+    }
+
+    /// Providers that may know `asset` in the order to try them, with the name to query each with
+    fn lookup(&self, asset: &str) -> Vec<(&dyn MarketCapProvider, String)> {
+        classify(self.resolve(asset))
+            .into_iter()
+            .filter_map(|(market_cap_type, name)| Some((self.provider_for(market_cap_type)?, name)))
+            .collect()
+    }
+
+    /// Market cap of `asset` in the currency of `options`
+    pub async fn fetch(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        let quote = self.fetch_native(transport, asset, options).await?;
+        let mut quotes = self.convert(transport, vec![quote], options).await?;
+        Ok(quotes.remove(0))
+    }
+
+    /// Market cap of `asset` in the currency the provider reports it in
+    async fn fetch_native(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        self.fetch_candidates(transport, asset, &self.lookup(asset), None, options)
+            .await
+    }
+
+    /// Quote of `asset` from the first of `candidates` that knows it. `first` is the result of
+    /// the first candidate if it was fetched already, e.g. in a batch with other assets.
+    async fn fetch_candidates(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        candidates: &[(&dyn MarketCapProvider, String)],
+        mut first: Option<Result<Quote>>,
+        options: &QuoteOptions,
+    ) -> Result<Quote> {
+        let mut tried = Vec::new();
+        for (provider, name) in candidates {
+            let result = match first.take() {
+                Some(result) => result,
+                None => provider.fetch(transport, name, options).await,
+            };
+            match result {
+                Ok(quote) => {
+                    return Ok(Quote {
+                        asset: asset.to_string(),
+                        ..quote
+                    })
+                }
+                Err(error) if candidates.len() == 1 || is_final(&error) => return Err(error),
+                Err(error) => tried.push(attempt(provider.market_cap_type(), name, error)),
+            }
+        }
+        Err(not_found(asset, tried))
+    }
+
+    /// Converts the quotes that are not in the currency of `options`, which providers report in
+    /// USD, with a single exchange rate lookup
+    async fn convert(
+        &self,
+        transport: &Transport,
+        mut quotes: Vec<Quote>,
+        options: &QuoteOptions,
+    ) -> Result<Vec<Quote>> {
+        let currency = options.currency();
+        if quotes.iter().all(|q| q.currency == currency) {
+            return Ok(quotes);
+        }
+        let exchange_rate = match &self.forex {
+            Some((base_urls, apikeys)) => {
+                return_exchange_rate(transport, base_urls, currency, options.date, apikeys).await?
+            }
+            None => return Err(Error::NoExchangeRate(currency.to_string()).into()),
+        };
+        for quote in quotes.iter_mut().filter(|q| q.currency != currency) {
+            quote.market_cap *= exchange_rate.rate;
+            quote.currency = currency.to_string();
+            quote.exchange_rate = Some(exchange_rate.clone());
+        }
+        Ok(quotes)
+    }
+
+    pub async fn history(
+        &self,
+        transport: &Transport,
+        asset: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Series> {
+        let providers = self.lookup(asset);
+        let mut tried = Vec::new();
+        for (provider, name) in &providers {
+            match provider.history(transport, name, from, to).await {
+                Ok(series) => return Ok(series),
+                Err(error) if providers.len() == 1 || is_final(&error) => return Err(error),
+                Err(error) => tried.push(attempt(provider.market_cap_type(), name, error)),
+            }
+        }
+        Err(not_found(asset, tried))
+    }
+
+    /// Shortest interval between repeated lookups of `assets` in the currency of `options` that
+    /// keeps every API within its rate limit
+    pub fn min_refresh_interval(&self, assets: &[String], options: &QuoteOptions) -> Duration {
+        // assets by their first candidate provider, as fetched by `fetch_all`
+        let mut batches: Vec<(&dyn MarketCapProvider, Vec<String>)> = Vec::new();
+        for asset in assets {
+            let (provider, asset) = match self.lookup(asset).into_iter().next() {
+                Some(found) => found,
+                None => continue,
+            };
+            match batches
+                .iter_mut()
+                .find(|(p, _)| p.market_cap_type() == provider.market_cap_type())
+            {
+                Some((_, names)) => names.push(asset),
+                None => batches.push((provider, vec![asset])),
+            }
+        }
+        // requests per minute and requests of one lookup per API
+        let mut requests: HashMap<&'static str, (u32, usize)> = HashMap::new();
+        for (provider, names) in &batches {
+            if let Some(limit) = self.rate_limit(*provider) {
+                let api = requests
+                    .entry(limit.api)
+                    .or_insert((limit.requests_per_minute, 0));
+                api.1 += provider.requests(names);
+            }
+        }
+        // market caps in another currency than USD take an exchange rate request
+        if let Some(limit) = self
+            .forex_rate_limit()
+            .filter(|_| options.currency() != USD)
+        {
+            let api = requests
+                .entry(limit.api)
+                .or_insert((limit.requests_per_minute, 0));
+            api.1 += 1;
+        }
+        requests
+            .values()
+            .map(|(requests_per_minute, requests)| {
+                let requests_per_minute = (*requests_per_minute).max(1) as u64;
+                Duration::from_secs((60 * *requests as u64).div_ceil(requests_per_minute))
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Fetches all assets concurrently and returns their quotes in the order of `assets`, in the
+    /// currency of `options`.
+    /// Assets hitting the same endpoint, e.g. `gold` and `Gold`, share one request and the
+    /// assets of a provider are fetched in a batch, see [`MarketCapProvider::fetch_many`].
+    pub async fn fetch_all(
+        &self,
+        transport: &Transport,
+        assets: &[String],
+        options: &QuoteOptions,
+    ) -> Result<Vec<Quote>> {
+        let mut endpoints: Vec<String> = Vec::new();
+        let mut unique_assets: Vec<&str> = Vec::new();
+        let mut indices = Vec::with_capacity(assets.len());
+        for asset in assets {
+            let endpoint = match self.lookup(asset).first() {
+                Some((provider, name)) => provider.endpoint(name),
+                None => asset.clone(),
+            };
+            let index = match endpoints.iter().position(|e| *e == endpoint) {
+                Some(index) => index,
+                None => {
+                    endpoints.push(endpoint);
+                    unique_assets.push(asset);
+                    endpoints.len() - 1
+                }
+            };
+            indices.push(index);
+        }
+        let candidates: Vec<_> = unique_assets.iter().map(|a| self.lookup(a)).collect();
+        // unique assets by their first candidate provider
+        let mut batches: Vec<(&dyn MarketCapProvider, Vec<usize>)> = Vec::new();
+        for (index, asset_candidates) in candidates.iter().enumerate() {
+            let provider = match asset_candidates.first() {
+                Some((provider, _)) => *provider,
+                None => continue,
+            };
+            match batches
+                .iter_mut()
+                .find(|(p, _)| p.market_cap_type() == provider.market_cap_type())
+            {
+                Some((_, indices)) => indices.push(index),
+                None => batches.push((provider, vec![index])),
+            }
+        }
+        let batch_results = try_join_all(batches.iter().map(|(provider, indices)| {
+            let names: Vec<String> = indices
+                .iter()
+                .map(|i| candidates[*i][0].1.clone())
+                .collect();
+            async move { provider.fetch_many(transport, &names, options).await }
+        }))
+        .await?;
+        let mut first: Vec<Option<Result<Quote>>> = unique_assets.iter().map(|_| None).collect();
+        for ((_, indices), results) in batches.iter().zip(batch_results) {
+            for (index, result) in indices.iter().zip(results) {
+                first[*index] = Some(result);
+            }
+        }
+        let quotes = try_join_all(unique_assets.iter().zip(&candidates).zip(first).map(
+            |((asset, asset_candidates), first)| {
+                self.fetch_candidates(transport, asset, asset_candidates, first, options)
+            },
+        ))
+        .await?;
+        let quotes = self.convert(transport, quotes, options).await?;
+        Ok(assets
+            .iter()
+            .zip(indices)
+            .map(|(asset, index)| Quote {
+                asset: asset.clone(),
+                ..quotes[index].clone()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{serve_response, serve_routes};
+    use crate::{COINGECKO_BASE_URL, POLYGONIO_BASE_URL};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const COINS_LIST: &str = r#"[{"id":"bitcoin","symbol":"btc","name":"Bitcoin"},{"id":"ethereum","symbol":"eth","name":"Ethereum"},{"id":"unranked-coin","symbol":"unr","name":"Unranked"},{"id":"delisted-coin","symbol":"del","name":"Delisted"}]"#;
+
+    /// Counts the requests it receives
+    #[derive(Default)]
+    struct CountingProvider {
+        requests: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl MarketCapProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn market_cap_type(&self) -> MarketCapType {
+            MarketCapType::Commodity
+        }
+
+        fn endpoint(&self, _asset: &str) -> String {
+            String::from("counting")
+        }
+
+        async fn fetch(
+            &self,
+            _transport: &Transport,
+            asset: &str,
+            _options: &QuoteOptions,
+        ) -> Result<Quote> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(Quote {
+                asset: asset.to_string(),
+                market_cap_type: self.market_cap_type(),
+                market_cap: 7.0,
+                currency: String::from(USD),
+                exchange_rate: None,
+                provider: self.name(),
+                note: None,
+            })
+        }
+    }
+
+    /// Crypto provider that knows no coin
+    struct FailingCryptoProvider;
+
+    #[async_trait]
+    impl MarketCapProvider for FailingCryptoProvider {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn market_cap_type(&self) -> MarketCapType {
+            MarketCapType::Crypto
+        }
+
+        async fn fetch(
+            &self,
+            _transport: &Transport,
+            asset: &str,
+            _options: &QuoteOptions,
+        ) -> Result<Quote> {
+            Err(Error::UnknownCoin(asset.to_string()).into())
+        }
+    }
+
+    /// Crypto provider on a plan without the requested date
+    struct HistoryLimitProvider;
+
+    #[async_trait]
+    impl MarketCapProvider for HistoryLimitProvider {
+        fn name(&self) -> &'static str {
+            "history-limit"
+        }
+
+        fn market_cap_type(&self) -> MarketCapType {
+            MarketCapType::Crypto
+        }
+
+        async fn fetch(
+            &self,
+            _transport: &Transport,
+            _asset: &str,
+            _options: &QuoteOptions,
+        ) -> Result<Quote> {
+            Err(Error::CoingeckoHistoryLimit.into())
+        }
+    }
+
+    /// Stock provider that knows no ticker
+    struct MissingProvider;
+
+    #[async_trait]
+    impl MarketCapProvider for MissingProvider {
+        fn name(&self) -> &'static str {
+            "missing"
+        }
+
+        fn market_cap_type(&self) -> MarketCapType {
+            MarketCapType::Stock
+        }
+
+        async fn fetch(
+            &self,
+            _transport: &Transport,
+            _asset: &str,
+            _options: &QuoteOptions,
+        ) -> Result<Quote> {
+            Err(Error::TickerNotFound(String::from("Ticker not found.")).into())
+        }
+    }
+
+    /// Stock provider whose requests fail, e.g. with a rate limit
+    struct BrokenStockProvider(fn() -> Error);
+
+    #[async_trait]
+    impl MarketCapProvider for BrokenStockProvider {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+
+        fn market_cap_type(&self) -> MarketCapType {
+            MarketCapType::Stock
+        }
+
+        async fn fetch(
+            &self,
+            _transport: &Transport,
+            _asset: &str,
+            _options: &QuoteOptions,
+        ) -> Result<Quote> {
+            Err(self.0().into())
+        }
+
+        async fn history(
+            &self,
+            _transport: &Transport,
+            _asset: &str,
+            _from: NaiveDate,
+            _to: NaiveDate,
+        ) -> Result<Series> {
+            Err(self.0().into())
+        }
+    }
+
+    /// Crypto provider reporting the same market cap for every asset
+    struct FixedProvider;
+
+    #[async_trait]
+    impl MarketCapProvider for FixedProvider {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn market_cap_type(&self) -> MarketCapType {
+            MarketCapType::Crypto
+        }
+
+        async fn fetch(
+            &self,
+            _transport: &Transport,
+            asset: &str,
+            _options: &QuoteOptions,
+        ) -> Result<Quote> {
+            Ok(Quote {
+                asset: asset.to_string(),
+                market_cap_type: self.market_cap_type(),
+                market_cap: 42.0,
+                currency: String::from(USD),
+                exchange_rate: None,
+                provider: self.name(),
+                note: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_identify_market_cap_type_for_gold() {
+        let m = String::from("gold");
+        assert_eq!(MarketCapType::Commodity, identify_market_cap_type(&m))
+    }
+    #[test]
+    fn test_identify_market_cap_type_for_capital_gold() {
+        let m = String::from("Gold");
+        assert_eq!(MarketCapType::Commodity, identify_market_cap_type(&m))
+    }
+    #[test]
+    fn test_identify_market_cap_type_for_silver() {
+        let m = String::from("silver");
+        assert_eq!(MarketCapType::Commodity, identify_market_cap_type(&m))
+    }
+    #[test]
+    fn test_identify_market_cap_type_for_crypto() {
+        let m = String::from("ethereum");
+        assert_eq!(MarketCapType::Crypto, identify_market_cap_type(&m))
+    }
+    #[test]
+    fn test_identify_market_cap_type_for_stock() {
+        let m = String::from("AAPL");
+        assert_eq!(MarketCapType::Stock, identify_market_cap_type(&m))
+    }
+    #[test]
+    fn test_identify_market_cap_type_with_prefix() {
+        assert_eq!(identify_market_cap_type("stock:GOLD"), MarketCapType::Stock);
+        assert_eq!(
+            identify_market_cap_type("crypto:BTC"),
+            MarketCapType::Crypto
+        );
+        assert_eq!(
+            identify_market_cap_type("commodity:GOLD"),
+            MarketCapType::Commodity
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("stock:GOLD"),
+            vec![(MarketCapType::Stock, String::from("GOLD"))]
+        );
+        assert_eq!(
+            classify("commodity:GOLD"),
+            vec![(MarketCapType::Commodity, String::from("gold"))]
+        );
+        assert_eq!(
+            classify("gold"),
+            vec![(MarketCapType::Commodity, String::from("gold"))]
+        );
+        assert_eq!(
+            classify("BITCOIN"),
+            vec![
+                (MarketCapType::Stock, String::from("BITCOIN")),
+                (MarketCapType::Crypto, String::from("BITCOIN")),
+            ]
+        );
+        assert_eq!(
+            classify("FooBar"),
+            vec![
+                (MarketCapType::Crypto, String::from("FooBar")),
+                (MarketCapType::Stock, String::from("FOOBAR")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identify_market_cap_type_for_unknown() {
+        let m = String::from("FooBar");
+        assert_eq!(MarketCapType::Unknown, identify_market_cap_type(&m))
+    }
+
+    #[test]
+    fn test_registry_default_providers() {
+        let registry = ProviderRegistry::with_defaults(
+            &BaseUrls::default(),
+            &ApiKeys::default(),
+            AboveGround::default(),
+        );
+        assert_eq!(
+            registry.provider_for(MarketCapType::Stock).unwrap().name(),
+            "polygon"
+        );
+        assert_eq!(
+            registry.provider_for(MarketCapType::Crypto).unwrap().name(),
+            "coingecko"
+        );
+        assert_eq!(
+            registry
+                .provider_for(MarketCapType::Commodity)
+                .unwrap()
+                .name(),
+            "commodity"
+        );
+        assert!(registry.provider_for(MarketCapType::Unknown).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_registry_register_replaces_provider() {
+        let mut registry = ProviderRegistry::with_defaults(
+            &BaseUrls::default(),
+            &ApiKeys::default(),
+            AboveGround::default(),
+        );
+        registry.register(FixedProvider);
+        let quote = registry
+            .fetch(
+                &Transport::default(),
+                &String::from("ethereum"),
+                &QuoteOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(quote.provider, "fixed");
+        assert_eq!(quote.market_cap, 42.0);
+    }
+
+    #[tokio::test]
+    async fn test_registry_fetch_unknown_asset() {
+        let registry = ProviderRegistry::new();
+        let result = registry
+            .fetch(
+                &Transport::default(),
+                &String::from("FooBar"),
+                &QuoteOptions::default(),
+            )
+            .await;
+        match result.unwrap_err().downcast::<Error>() {
+            Ok(Error::UnknownAssetName(name)) => assert_eq!(name, "FooBar"),
+            _ => panic!("Expected Error::UnknownAssetName"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_provider_against_local_server() {
+        let (base_url, _) = serve_routes(vec![("/api/v3/coins/list", COINS_LIST), ("/api/v3/coins/markets", r#"[{"id":"bitcoin","symbol":"btc","name":"Bitcoin","image":"","current_price":1.0,"market_cap":1000.0,"market_cap_rank":1,"fully_diluted_valuation":1000,"total_volume":1.0,"high_24h":1.0,"low_24h":1.0,"price_change_24h":0.0,"price_change_percentage_24h":0.0,"market_cap_change_24h":0.0,"market_cap_change_percentage_24h":0.0,"circulating_supply":1.0,"total_supply":1.0,"max_supply":null,"ath":1.0,"ath_change_percentage":0.0,"ath_date":"","atl":1.0,"atl_change_percentage":0.0,"atl_date":"","roi":null,"last_updated":""}]"#)]).await;
+        let base_urls = BaseUrls::new(POLYGONIO_BASE_URL, &base_url).unwrap();
+        let provider = CoinGeckoProvider::new(base_urls, ApiKeys::default());
+        let quote = provider
+            .fetch(&Transport::default(), "bitcoin", &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(quote.market_cap, 1000.0);
+    }
+
+    #[tokio::test]
+    async fn test_polygon_provider_reports_api_error_from_local_server() {
+        let base_url = serve_response(
+            "404 Not Found",
+            r#"{"status":"NOT_FOUND","request_id":"1","message":"Ticker not found."}"#,
+        )
+        .await;
+        let base_urls = BaseUrls::new(&base_url, COINGECKO_BASE_URL).unwrap();
+        let provider =
+            PolygonProvider::new(base_urls, ApiKeys::new(Some(String::from("key")), None));
+        let result = provider
+            .fetch(&Transport::default(), "FOOBAR", &QuoteOptions::default())
+            .await;
+        match result.unwrap_err().downcast::<Error>() {
+            Ok(Error::TickerNotFound(message)) => assert_eq!(message, "Ticker not found."),
+            _ => panic!("Expected Error::TickerNotFound"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_fetch_all_deduplicates_endpoints() {
+        let provider = CountingProvider::default();
+        let requests = provider.requests.clone();
+        let mut registry = ProviderRegistry::new();
+        registry.register(provider);
+        registry.register(FixedProvider);
+        let assets = vec![
+            String::from("gold"),
+            String::from("ethereum"),
+            String::from("Gold"),
+        ];
+        let quotes = registry
+            .fetch_all(&Transport::default(), &assets, &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        let names: Vec<&str> = quotes.iter().map(|q| q.asset.as_str()).collect();
+        assert_eq!(names, vec!["gold", "ethereum", "Gold"]);
+        assert_eq!(quotes[2].market_cap, 7.0);
+        assert_eq!(quotes[1].market_cap, 42.0);
+    }
+
+    #[tokio::test]
+    async fn test_provider_without_history() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let result = FixedProvider
+            .history(&Transport::default(), "ethereum", date, date)
+            .await;
+        match result.unwrap_err().downcast::<Error>() {
+            Ok(Error::HistoryUnsupported(name)) => assert_eq!(name, "ethereum"),
+            _ => panic!("Expected Error::HistoryUnsupported"),
+        }
+    }
+
+    #[test]
+    fn test_min_refresh_interval_shares_polygon_quota() {
+        let registry = ProviderRegistry::with_defaults(
+            &BaseUrls::default(),
+            &ApiKeys::default(),
+            AboveGround::default(),
+        );
+        let assets: Vec<String> = ["AAPL", "gold", "Gold", "silver", "bitcoin"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        // AAPL, gold and silver are three requests to polygon.io, which allows 5 per minute
+        assert_eq!(
+            registry.min_refresh_interval(&assets, &QuoteOptions::default()),
+            Duration::from_secs(36)
+        );
+        // converting them takes a fourth request for the exchange rate
+        let options = QuoteOptions {
+            currency: Some(String::from("EUR")),
+            ..Default::default()
+        };
+        assert_eq!(
+            registry.min_refresh_interval(&assets, &options),
+            Duration::from_secs(48)
+        );
+        // the latest market caps of all coins are one request, the public coingecko API allows
+        // 10 requests per minute, a demo key 30
+        let assets = vec![String::from("bitcoin"), String::from("ethereum")];
+        assert_eq!(
+            registry.min_refresh_interval(&assets, &QuoteOptions::default()),
+            Duration::from_secs(6)
+        );
+        let coins: Vec<String> = (0..10).map(|i| format!("coin-{i}")).collect();
+        assert_eq!(
+            registry.min_refresh_interval(&coins, &QuoteOptions::default()),
+            Duration::from_secs(6)
+        );
+        let coins: Vec<String> = (0..101).map(|i| format!("coin-{i}")).collect();
+        assert_eq!(
+            registry.min_refresh_interval(&coins, &QuoteOptions::default()),
+            Duration::from_secs(12)
+        );
+        let registry = ProviderRegistry::with_defaults(
+            &BaseUrls::default(),
+            &ApiKeys::new(None, Some(String::from("key"))),
+            AboveGround::default(),
+        );
+        assert_eq!(
+            registry.min_refresh_interval(&assets, &QuoteOptions::default()),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn test_rate_limits_apply_overrides() {
+        let registry = ProviderRegistry::with_defaults(
+            &BaseUrls::default(),
+            &ApiKeys::default(),
+            AboveGround::default(),
+        )
+        .with_rate_limits(&[RateLimit {
+            api: POLYGON_API,
+            requests_per_minute: 100,
+        }]);
+        assert_eq!(
+            registry.rate_limits(),
+            vec![
+                RateLimit {
+                    api: COINGECKO_API,
+                    requests_per_minute: 10,
+                },
+                RateLimit {
+                    api: POLYGON_API,
+                    requests_per_minute: 100,
+                },
+            ]
+        );
+        let assets = vec![String::from("AAPL"), String::from("gold")];
+        assert_eq!(
+            registry.min_refresh_interval(&assets, &QuoteOptions::default()),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_converts_usd_market_caps() {
+        let base_url = serve_response(
+            "200 OK",
+            r#"{"ticker":"C:USDEUR","queryCount":1,"resultsCount":1,"adjusted":true,"results":[{"T":"C:USDEUR","v":1,"o":0.9,"c":0.9,"h":0.9,"l":0.9,"t":1726703999999}],"status":"OK","request_id":"1","count":1}"#,
+        )
+        .await;
+        let mut registry = ProviderRegistry::new();
+        registry.register(FixedProvider);
+        registry.forex = Some((
+            BaseUrls::new(&base_url, &base_url).unwrap(),
+            ApiKeys::new(Some(String::from("key")), None),
+        ));
+        let options = QuoteOptions {
+            currency: Some(String::from("EUR")),
+            ..Default::default()
+        };
+        let quote = registry
+            .fetch(&Transport::default(), "ethereum", &options)
+            .await
+            .unwrap();
+        assert_eq!(quote.market_cap, 42.0 * 0.9);
+        assert_eq!(quote.currency, "EUR");
+        let exchange_rate = quote.exchange_rate.unwrap();
+        assert_eq!(exchange_rate.rate, 0.9);
+        assert_eq!(exchange_rate.time.timestamp_millis(), 1726703999999);
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_fetch_many_maps_markets_by_id() {
+        // one response for all coins, in an order of its own, without delisted coins and with null
+        // for coins without a market cap
+        let (base_url, _) = serve_routes(vec![
+            ("/api/v3/coins/list", COINS_LIST),
+            (
+                "/api/v3/coins/markets",
+                r#"[{"id":"bitcoin","symbol":"btc","market_cap":1.2e12},{"id":"ethereum","symbol":"eth","market_cap":3.0e11},{"id":"unranked-coin","symbol":"unr","market_cap":null}]"#,
+            ),
+        ])
+        .await;
+        let provider = CoinGeckoProvider::new(
+            BaseUrls::new(&base_url, &base_url).unwrap(),
+            ApiKeys::default(),
+        );
+        let assets: Vec<String> = ["ethereum", "btc", "delisted-coin", "unranked-coin", "doge"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let quotes = provider
+            .fetch_many(&Transport::default(), &assets, &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(quotes[0].as_ref().unwrap().market_cap, 3.0e11);
+        assert_eq!(quotes[1].as_ref().unwrap().market_cap, 1.2e12);
+        match quotes[2].as_ref().unwrap_err().downcast_ref::<Error>() {
+            Some(Error::NoData(id)) => assert_eq!(id, "delisted-coin"),
+            _ => panic!("Expected Error::NoData"),
+        }
+        assert!(quotes[3].is_err());
+        match quotes[4].as_ref().unwrap_err().downcast_ref::<Error>() {
+            Some(Error::UnknownCoin(asset)) => assert_eq!(asset, "doge"),
+            _ => panic!("Expected Error::UnknownCoin"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_batches_crypto_assets() {
+        let (base_url, requests) = serve_routes(vec![
+            ("/api/v3/coins/list", COINS_LIST),
+            (
+                "/api/v3/coins/markets",
+                r#"[{"id":"bitcoin","market_cap":1.2e12},{"id":"ethereum","market_cap":3.0e11}]"#,
+            ),
+        ])
+        .await;
+        let mut registry = ProviderRegistry::new();
+        registry.register(CoinGeckoProvider::new(
+            BaseUrls::new(&base_url, &base_url).unwrap(),
+            ApiKeys::default(),
+        ));
+        let assets = vec![String::from("ETH"), String::from("bitcoin")];
+        let quotes = registry
+            .fetch_all(&Transport::default(), &assets, &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(quotes[0].asset, "ETH");
+        assert_eq!(quotes[0].market_cap, 3.0e11);
+        assert_eq!(quotes[1].asset, "bitcoin");
+        assert_eq!(quotes[1].market_cap, 1.2e12);
+        // one coin list and one markets request for both assets
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_registry_without_exchange_rates() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(FixedProvider);
+        let options = QuoteOptions {
+            currency: Some(String::from("CHF")),
+            ..Default::default()
+        };
+        let result = registry
+            .fetch(&Transport::default(), "ethereum", &options)
+            .await;
+        match result.unwrap_err().downcast::<Error>() {
+            Ok(Error::NoExchangeRate(currency)) => assert_eq!(currency, "CHF"),
+            _ => panic!("Expected Error::NoExchangeRate"),
+        }
+        let quote = registry
+            .fetch(&Transport::default(), "ethereum", &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(quote.market_cap, 42.0);
+    }
+
+    #[tokio::test]
+    async fn test_registry_resolves_aliases() {
+        let provider = CountingProvider::default();
+        let requests = provider.requests.clone();
+        let mut registry =
+            ProviderRegistry::new().with_aliases([(String::from("au"), String::from("gold"))]);
+        registry.register(provider);
+        let assets = vec![String::from("au"), String::from("gold")];
+        let quotes = registry
+            .fetch_all(&Transport::default(), &assets, &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(quotes[0].asset, "au");
+        assert_eq!(registry.resolve("au"), "gold");
+        assert_eq!(registry.resolve("silver"), "silver");
+    }
+
+    #[tokio::test]
+    async fn test_registry_falls_back_to_other_classification() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(MissingProvider);
+        registry.register(FixedProvider);
+        let quote = registry
+            .fetch(&Transport::default(), "BITCOIN", &QuoteOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(quote.provider, "fixed");
+        assert_eq!(quote.asset, "BITCOIN");
+    }
+
+    #[tokio::test]
+    async fn test_registry_explicit_prefix_skips_fallback() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(MissingProvider);
+        registry.register(FixedProvider);
+        let result = registry
+            .fetch(
+                &Transport::default(),
+                "stock:BITCOIN",
+                &QuoteOptions::default(),
+            )
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast::<Error>(),
+            Ok(Error::TickerNotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_registry_history_limit_skips_fallback() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(HistoryLimitProvider);
+        registry.register(MissingProvider);
+        let result = registry
+            .fetch(&Transport::default(), "bitcoin", &QuoteOptions::default())
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast::<Error>(),
+            Ok(Error::CoingeckoHistoryLimit)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_registry_rate_limit_skips_fallback() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(BrokenStockProvider(|| {
+            Error::RateLimited(String::from("api.polygon.io"), None)
+        }));
+        registry.register(FixedProvider);
+        let result = registry
+            .fetch(&Transport::default(), "GOLD", &QuoteOptions::default())
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast::<Error>(),
+            Ok(Error::RateLimited(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_registry_missing_key_skips_fallback() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(BrokenStockProvider(|| {
+            Error::EnvVarError(String::from("POLYGON_KEY"))
+        }));
+        registry.register(FixedProvider);
+        let result = registry
+            .history(
+                &Transport::default(),
+                "AAPL",
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+            )
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast::<Error>(),
+            Ok(Error::EnvVarError(..))
+        ));
+        let result = registry
+            .fetch(&Transport::default(), "AAPL", &QuoteOptions::default())
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast::<Error>(),
+            Ok(Error::EnvVarError(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_registry_not_found_lists_classifications_tried() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(MissingProvider);
+        registry.register(FailingCryptoProvider);
+        let error = registry
+            .fetch(&Transport::default(), "FooBar", &QuoteOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Could not find FooBar, tried crypto FooBar (No coin on coingecko has the id, symbol or name FooBar), stock FOOBAR (Polygon API error: Ticker not found.)\n"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::RateLimit;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Token bucket holding up to a minute's quota, refilled continuously. Requests may take tokens
+/// in advance, leaving the bucket negative, so later requests queue behind earlier ones.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_minute: u32, now: Instant) -> Self {
+        let capacity = requests_per_minute.max(1) as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            per_second: capacity / 60.0,
+            updated: now,
+        }
+    }
+
+    /// Takes a token, returns how long to wait until it is available
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+/// Requests waiting for a quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStatus {
+    /// Number of requests waiting
+    pub waiting: usize,
+    /// When the last waiting request is sent, unset while none waits
+    pub ready_at: Option<Instant>,
+}
+
+type Observer = Box<dyn Fn(&QueueStatus) + Send + Sync>;
+
+/// Schedules requests within the quota of each API. Requests exceeding a quota wait until it
+/// allows them instead of being rejected by the API.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: HashMap<&'static str, Mutex<TokenBucket>>,
+    status: Mutex<QueueStatus>,
+    observer: Option<Observer>,
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("buckets", &self.buckets)
+            .field("status", &self.status)
+            .finish()
+    }
+}
+
+impl RateLimiter {
+    /// Limiter enforcing `limits`, requests to other APIs are sent at once
+    pub fn new(limits: &[RateLimit]) -> Self {
+        let now = Instant::now();
+        let buckets = limits
+            .iter()
+            .map(|limit| {
+                let bucket = TokenBucket::new(limit.requests_per_minute, now);
+                (limit.api, Mutex::new(bucket))
+            })
+            .collect();
+        Self {
+            buckets,
+            ..Default::default()
+        }
+    }
+
+    /// Calls `observer` whenever a request starts or stops waiting, e.g. to show progress
+    pub fn with_observer(
+        mut self,
+        observer: impl Fn(&QueueStatus) + Send + Sync + 'static,
+    ) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Waits until the quota of `api` allows another request
+    pub async fn acquire(&self, api: &str) {
+        let bucket = match self.buckets.get(api) {
+            Some(bucket) => bucket,
+            None => return,
+        };
+        let now = Instant::now();
+        let wait = bucket.lock().unwrap().reserve(now);
+        if wait.is_zero() {
+            return;
+        }
+        self.update(|status| {
+            status.waiting += 1;
+            status.ready_at = status.ready_at.max(Some(now + wait));
+        });
+        // leaves the queue even if the request is dropped while waiting
+        let _waiting = Waiting(self);
+        tokio::time::sleep(wait).await;
+    }
+
+    fn update(&self, change: impl FnOnce(&mut QueueStatus)) {
+        let mut status = self.status.lock().unwrap();
+        change(&mut status);
+        if let Some(observer) = &self.observer {
+            observer(&status);
+        }
+    }
+}
+
+/// A request waiting in the queue of a [`RateLimiter`]
+struct Waiting<'a>(&'a RateLimiter);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.update(|status| {
+            status.waiting -= 1;
+            if status.waiting == 0 {
+                status.ready_at = None;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::COINGECKO_API;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_token_bucket_spreads_requests_beyond_quota() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(5, now);
+        for _ in 0..5 {
+            assert_eq!(bucket.reserve(now), Duration::ZERO);
+        }
+        // one token every 12s, queued requests wait for the ones before them
+        assert_eq!(bucket.reserve(now), Duration::from_secs(12));
+        assert_eq!(bucket.reserve(now), Duration::from_secs(24));
+        assert_eq!(
+            bucket.reserve(now + Duration::from_secs(60)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_token_bucket_refills_up_to_capacity() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(30, now);
+        bucket.reserve(now);
+        bucket.reserve(now + Duration::from_secs(3600));
+        assert_eq!(bucket.tokens, 29.0);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_reports_queued_requests() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let observed = seen.clone();
+        let limiter = RateLimiter::new(&[RateLimit {
+            api: COINGECKO_API,
+            requests_per_minute: 600,
+        }])
+        .with_observer(move |status| observed.lock().unwrap().push(status.waiting));
+        for _ in 0..601 {
+            limiter.acquire(COINGECKO_API).await;
+        }
+        // a request to an API without a quota is not limited
+        limiter.acquire("example.com").await;
+        // only the request exceeding the minute's quota waited, 100ms for the next token
+        assert_eq!(*seen.lock().unwrap(), vec![1, 0]);
+        assert_eq!(*limiter.status.lock().unwrap(), QueueStatus::default());
+    }
+}
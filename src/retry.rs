@@ -0,0 +1,179 @@
+use crate::provider::{COINGECKO, COINS, COMMODITY, FOREX, POLYGON};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// When and how often a failed request is sent again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, zero disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every further retry
+    pub base_delay: Duration,
+    /// Longest delay before a retry. A `Retry-After` beyond it fails the request at once.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub const fn new(max_retries: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+            max_delay,
+        }
+    }
+
+    /// Delay before retry number `retry`, counted from zero. `Retry-After` of the response is
+    /// used as is, otherwise the exponential delay is jittered between half and all of it so
+    /// that concurrent requests don't retry in lockstep. `None` if the request should fail.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if retry >= self.max_retries {
+            return None;
+        }
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        Some(exponential / 2 + jitter(exponential / 2))
+    }
+}
+
+/// Rate limit responses of polygon.io's free plan only clear once its per minute window passed
+const POLYGON_POLICY: RetryPolicy =
+    RetryPolicy::new(3, Duration::from_secs(15), Duration::from_secs(60));
+const COINGECKO_POLICY: RetryPolicy =
+    RetryPolicy::new(3, Duration::from_secs(2), Duration::from_secs(60));
+const FALLBACK_POLICY: RetryPolicy =
+    RetryPolicy::new(3, Duration::from_secs(1), Duration::from_secs(30));
+
+/// Default policy per provider
+const DEFAULT_POLICIES: &[(&str, RetryPolicy)] = &[
+    (POLYGON.name, POLYGON_POLICY),
+    (COMMODITY.name, POLYGON_POLICY),
+    (FOREX.name, POLYGON_POLICY),
+    (COINGECKO.name, COINGECKO_POLICY),
+    (COINS.name, COINGECKO_POLICY),
+];
+
+/// Retry policy per provider name, providers without a policy use a fallback
+#[derive(Debug, Clone)]
+pub struct RetryPolicies {
+    policies: HashMap<String, RetryPolicy>,
+}
+
+impl Default for RetryPolicies {
+    fn default() -> Self {
+        let policies = DEFAULT_POLICIES
+            .iter()
+            .map(|(provider, policy)| (provider.to_string(), *policy))
+            .collect();
+        Self { policies }
+    }
+}
+
+impl RetryPolicies {
+    pub fn with_policy(mut self, provider: &str, policy: RetryPolicy) -> Self {
+        self.policies.insert(provider.to_string(), policy);
+        self
+    }
+
+    pub fn get(&self, provider: &str) -> RetryPolicy {
+        self.policies
+            .get(provider)
+            .copied()
+            .unwrap_or(FALLBACK_POLICY)
+    }
+}
+
+/// Rate limit or a server side failure that may succeed when sent again
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Parses a `Retry-After` header, either seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Random duration up to `max`
+fn jitter(max: Duration) -> Duration {
+    // every RandomState is seeded with fresh random keys
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const POLICY: RetryPolicy = RetryPolicy::new(3, Duration::from_secs(2), Duration::from_secs(5));
+
+    #[test]
+    fn test_delay_doubles_with_jitter() {
+        for (retry, max) in [(0, 2), (1, 4), (2, 5)] {
+            let delay = POLICY.delay(retry, None).unwrap();
+            let max = Duration::from_secs(max);
+            assert!(delay >= max / 2 && delay <= max, "{retry}: {delay:?}");
+        }
+        assert_eq!(POLICY.delay(3, None), None);
+    }
+
+    #[test]
+    fn test_delay_uses_retry_after() {
+        let retry_after = Some(Duration::from_secs(4));
+        assert_eq!(POLICY.delay(0, retry_after), retry_after);
+        assert_eq!(POLICY.delay(0, Some(Duration::from_secs(6))), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 30).unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_policy_per_provider() {
+        let policies = RetryPolicies::default().with_policy("coingecko", POLICY);
+        assert_eq!(policies.get("coingecko"), POLICY);
+        assert_eq!(policies.get("forex"), POLYGON_POLICY);
+        assert_eq!(policies.get("unknown"), FALLBACK_POLICY);
+    }
+
+    #[test]
+    fn test_retryable_status() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+    }
+}
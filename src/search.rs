@@ -0,0 +1,298 @@
+use crate::coins::{coin_list, Coin};
+use crate::provider::POLYGON;
+use crate::{
+    classify, ApiKeys, BaseUrls, Commodity, Error, MarketCapType, PolygonIoErrorResponse, Transport,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use url::Url;
+
+/// Entry of polygon.io's `/v3/reference/tickers` search
+#[derive(Deserialize, Debug)]
+struct TickerSearchResult {
+    ticker: String,
+    name: String,
+    primary_exchange: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TickerSearchV3 {
+    #[serde(default)]
+    results: Vec<TickerSearchResult>,
+}
+
+/// An asset matching a search, `identifier` can be passed to a comparison as is
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchMatch {
+    pub market_cap_type: MarketCapType,
+    pub identifier: String,
+    pub name: String,
+    /// Primary exchange of stocks as MIC, e.g. XNAS
+    pub exchange: Option<String>,
+}
+
+/// Matches of a search, with a note for each asset class that could not be searched
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    pub matches: Vec<SearchMatch>,
+    pub notes: Vec<String>,
+}
+
+/// Active stock tickers whose symbol or name contains `query`
+fn construct_ticker_search_query_url(
+    base_url: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Url, Error> {
+    match Url::from_str(&format!("{base_url}/v3/reference/tickers")) {
+        Ok(mut url) => {
+            url.query_pairs_mut()
+                .append_pair("search", query)
+                .append_pair("market", "stocks")
+                .append_pair("active", "true")
+                .append_pair("limit", &limit.to_string());
+            Ok(url)
+        }
+        Err(error) => Err(Error::InvalidUrl(error)),
+    }
+}
+
+/// How well `candidate` matches the lower case `query`, lower is better: equal, prefix, substring
+/// or all characters of the query in order
+fn fuzzy_score(candidate: &str, query: &str) -> Option<u8> {
+    let candidate = candidate.to_lowercase();
+    if candidate == query {
+        Some(0)
+    } else if candidate.starts_with(query) {
+        Some(1)
+    } else if candidate.contains(query) {
+        Some(2)
+    } else {
+        let mut chars = candidate.chars();
+        query.chars().all(|q| chars.any(|c| c == q)).then_some(3)
+    }
+}
+
+/// Up to `limit` coins whose id, symbol or name fuzzily match `query`, best matches first
+pub fn search_coins<'a>(coins: &'a [Coin], query: &str, limit: usize) -> Vec<&'a Coin> {
+    let query = query.to_lowercase();
+    let mut matches: Vec<(u8, &Coin)> = coins
+        .iter()
+        .filter_map(|coin| {
+            [&coin.id, &coin.symbol, &coin.name]
+                .iter()
+                .filter_map(|field| fuzzy_score(field, &query))
+                .min()
+                .map(|score| (score, coin))
+        })
+        .collect();
+    matches.sort_by_key(|(score, coin)| (*score, coin.name.len()));
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, coin)| coin)
+        .collect()
+}
+
+/// `id` with a prefix if the case heuristic would take it for another type of asset
+fn identifier(market_cap_type: MarketCapType, id: &str) -> String {
+    match classify(id).first() {
+        Some((guess, _)) if *guess == market_cap_type => id.to_string(),
+        _ => format!("{market_cap_type}:{id}"),
+    }
+}
+
+async fn search_stocks(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    query: &str,
+    limit: usize,
+    apikeys: &ApiKeys,
+) -> Result<Vec<SearchMatch>> {
+    let url = construct_ticker_search_query_url(&base_urls.polygon, query, limit)?;
+    let authorization = apikeys.polygonio_authorization()?;
+    let response = transport
+        .get_with_headers(POLYGON, url, &[("Authorization", &authorization)])
+        .await?;
+    if response.status.is_success() {
+        let search: TickerSearchV3 = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, query.to_string()))?;
+        Ok(search
+            .results
+            .into_iter()
+            .map(|result| SearchMatch {
+                market_cap_type: MarketCapType::Stock,
+                identifier: identifier(MarketCapType::Stock, &result.ticker),
+                name: result.name,
+                exchange: result.primary_exchange,
+            })
+            .collect())
+    } else {
+        let error_json: PolygonIoErrorResponse = serde_json::from_str(&response.body)
+            .map_err(|e| Error::Deserialization(e, query.to_string()))?;
+        Err(Error::PolygonApi(error_json.message).into())
+    }
+}
+
+async fn search_crypto(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    query: &str,
+    limit: usize,
+    apikeys: &ApiKeys,
+) -> Result<Vec<SearchMatch>> {
+    let coins = coin_list(transport, base_urls, apikeys).await?;
+    Ok(search_coins(&coins, query, limit)
+        .into_iter()
+        .map(|coin| SearchMatch {
+            market_cap_type: MarketCapType::Crypto,
+            identifier: identifier(MarketCapType::Crypto, &coin.id),
+            name: format!("{} ({})", coin.name, coin.symbol.to_uppercase()),
+            exchange: None,
+        })
+        .collect())
+}
+
+/// Commodities, stocks and cryptocurrencies matching `query`, up to `limit` of each of the
+/// latter two. The asset classes are searched independently, one that fails or needs a missing
+/// API key is skipped with a note.
+pub async fn search(
+    transport: &Transport,
+    base_urls: &BaseUrls,
+    query: &str,
+    limit: usize,
+    apikeys: &ApiKeys,
+) -> SearchResults {
+    let lowercase = query.to_lowercase();
+    let mut results = SearchResults {
+        matches: Commodity::ALL
+            .iter()
+            .filter(|commodity| fuzzy_score(commodity.name(), &lowercase).is_some())
+            .map(|commodity| SearchMatch {
+                market_cap_type: MarketCapType::Commodity,
+                identifier: identifier(MarketCapType::Commodity, commodity.name()),
+                name: commodity.name().to_string(),
+                exchange: None,
+            })
+            .collect(),
+        notes: Vec::new(),
+    };
+    let stocks = async {
+        if apikeys.has_polygonio() {
+            Some(search_stocks(transport, base_urls, query, limit, apikeys).await)
+        } else {
+            None
+        }
+    };
+    let (stocks, crypto) = futures::join!(
+        stocks,
+        search_crypto(transport, base_urls, query, limit, apikeys),
+    );
+    match stocks {
+        Some(stocks) => results.add("stocks", stocks),
+        None => results.notes.push(String::from(
+            "stocks not searched, set POLYGON_KEY or add api_key to the config file",
+        )),
+    }
+    results.add("cryptocurrencies", crypto);
+    results
+}
+
+impl SearchResults {
+    fn add(&mut self, asset_class: &str, matches: Result<Vec<SearchMatch>>) {
+        match matches {
+            Ok(matches) => self.matches.extend(matches),
+            Err(error) => {
+                let error = error.to_string().trim().replace('\n', " ");
+                self.notes
+                    .push(format!("{asset_class} not searched: {error}"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::coin;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("Bitcoin", "bitcoin"), Some(0));
+        assert_eq!(fuzzy_score("bitcoin-cash", "bitcoin"), Some(1));
+        assert_eq!(fuzzy_score("wrapped-bitcoin", "bitcoin"), Some(2));
+        assert_eq!(fuzzy_score("bitcoin", "btc"), Some(3));
+        assert_eq!(fuzzy_score("ethereum", "btc"), None);
+    }
+
+    #[test]
+    fn test_search_coins_orders_best_matches_first() {
+        let coins = vec![
+            coin("wrapped-bitcoin", "wbtc", "Wrapped Bitcoin"),
+            coin("bitcoin-cash", "bch", "Bitcoin Cash"),
+            coin("ethereum", "eth", "Ethereum"),
+            coin("bitcoin", "btc", "Bitcoin"),
+        ];
+        let ids: Vec<&str> = search_coins(&coins, "Bitcoin", 10)
+            .iter()
+            .map(|coin| coin.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["bitcoin", "bitcoin-cash", "wrapped-bitcoin"]);
+        assert_eq!(search_coins(&coins, "bitcoin", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_identifier_adds_prefix_only_when_needed() {
+        assert_eq!(identifier(MarketCapType::Stock, "AAPL"), "AAPL");
+        assert_eq!(identifier(MarketCapType::Crypto, "bitcoin"), "bitcoin");
+        assert_eq!(identifier(MarketCapType::Commodity, "gold"), "gold");
+        assert_eq!(identifier(MarketCapType::Crypto, "GOLD"), "crypto:GOLD");
+        assert_eq!(identifier(MarketCapType::Stock, "gold"), "stock:gold");
+    }
+
+    #[tokio::test]
+    async fn test_search_without_polygon_key_skips_stocks() {
+        let dir = std::env::temp_dir().join(format!("ratio-gang-search-{}", std::process::id()));
+        let transport = Transport::new(
+            reqwest::Client::new(),
+            crate::transport::Mode::Replay(dir.clone()),
+        );
+        let results = search(
+            &transport,
+            &BaseUrls::default(),
+            "gold",
+            10,
+            &ApiKeys::default(),
+        )
+        .await;
+        assert_eq!(results.matches[0].identifier, "gold");
+        assert_eq!(results.notes.len(), 2);
+        assert!(results.notes[0].starts_with("stocks not searched, set POLYGON_KEY"));
+        // no fixture for the coin list in the replay directory
+        assert!(results.notes[1].starts_with("cryptocurrencies not searched"));
+    }
+
+    #[test]
+    fn test_construct_ticker_search_query_url() {
+        let url =
+            construct_ticker_search_query_url("https://api.polygon.io", "apple inc", 5).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://api.polygon.io/v3/reference/tickers?search=apple+inc&market=stocks&active=true&limit=5"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_ticker_search_v3() {
+        let search: TickerSearchV3 = serde_json::from_str(
+            r#"{"results":[{"ticker":"AAPL","name":"Apple Inc.","market":"stocks","locale":"us","primary_exchange":"XNAS","type":"CS","active":true}],"status":"OK","request_id":"x","count":1}"#,
+        )
+        .unwrap();
+        assert_eq!(search.results[0].ticker, "AAPL");
+        assert_eq!(search.results[0].primary_exchange.as_deref(), Some("XNAS"));
+        let empty: TickerSearchV3 =
+            serde_json::from_str(r#"{"status":"OK","request_id":"x","count":0}"#).unwrap();
+        assert!(empty.results.is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+//! Fixtures shared by the tests of several modules
+
+use crate::coins::Coin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+mod quote;
+
+pub use quote::quote;
+
+/// Entry of the coingecko coin list
+pub fn coin(id: &str, symbol: &str, name: &str) -> Coin {
+    Coin {
+        id: id.to_string(),
+        symbol: symbol.to_string(),
+        name: name.to_string(),
+    }
+}
+
+/// Serves HTTP requests on a random local port with the raw response `respond` returns for the
+/// number of the request, counted from zero, and its path. Returns the base URL and the number of
+/// requests served.
+pub async fn serve(
+    respond: impl Fn(usize, &str) -> String + Send + 'static,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let served = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let index = served.fetch_add(1, Ordering::SeqCst);
+            let mut buf = [0u8; 4096];
+            let read = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..read]);
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            let response = respond(index, path);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (format!("http://{addr}"), requests)
+}
+
+/// Raw HTTP response with a json body
+pub fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serves every request with `status` and `body`, returns the base URL
+pub async fn serve_response(status: &'static str, body: &'static str) -> String {
+    serve(move |_, _| response(status, body)).await.0
+}
+
+/// Serves each request with the body of the first route whose path prefix it starts with,
+/// returns the base URL and the number of requests served
+pub async fn serve_routes(routes: Vec<(&'static str, &'static str)>) -> (String, Arc<AtomicUsize>) {
+    serve(move |_, path| {
+        let body = routes
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix))
+            .map_or("[]", |(_, body)| body);
+        response("200 OK", body)
+    })
+    .await
+}
@@ -0,0 +1,16 @@
+//! Quote fixture, also included by the tests of the binary
+
+use ratio_gang_cli::{MarketCapType, Quote};
+
+/// Crypto market cap in USD
+pub fn quote(asset: &str, market_cap: f64) -> Quote {
+    Quote {
+        asset: asset.to_string(),
+        market_cap_type: MarketCapType::Crypto,
+        market_cap,
+        currency: String::from("USD"),
+        exchange_rate: None,
+        provider: "test",
+        note: None,
+    }
+}
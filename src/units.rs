@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Troy ounce, the unit precious metals are quoted in, not the avoirdupois ounce of 28.35 g
+const GRAMS_PER_TROY_OUNCE: f64 = 31.1034768;
+const GRAMS_PER_KILOGRAM: f64 = 1000.0;
+const GRAMS_PER_TONNE: f64 = 1_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MassUnit {
+    /// Metric tonne
+    Tonne,
+    TroyOunce,
+    Kilogram,
+}
+
+impl MassUnit {
+    fn grams(&self) -> f64 {
+        match self {
+            MassUnit::Tonne => GRAMS_PER_TONNE,
+            MassUnit::TroyOunce => GRAMS_PER_TROY_OUNCE,
+            MassUnit::Kilogram => GRAMS_PER_KILOGRAM,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            MassUnit::Tonne => "t",
+            MassUnit::TroyOunce => "oz",
+            MassUnit::Kilogram => "kg",
+        }
+    }
+}
+
+impl FromStr for MassUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "t" | "tonne" | "tonnes" => Ok(MassUnit::Tonne),
+            "oz" | "ozt" | "troy-oz" => Ok(MassUnit::TroyOunce),
+            "kg" => Ok(MassUnit::Kilogram),
+            _ => Err(format!("unknown mass unit '{s}', use t, oz or kg")),
+        }
+    }
+}
+
+/// Mass of a commodity, keeping the unit it was given in for display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mass {
+    value: f64,
+    unit: MassUnit,
+}
+
+impl Mass {
+    pub fn new(value: f64, unit: MassUnit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn tonnes(value: f64) -> Self {
+        Self::new(value, MassUnit::Tonne)
+    }
+
+    pub fn troy_ounces(value: f64) -> Self {
+        Self::new(value, MassUnit::TroyOunce)
+    }
+
+    pub fn kilograms(value: f64) -> Self {
+        Self::new(value, MassUnit::Kilogram)
+    }
+
+    /// The mass expressed in `unit`
+    pub fn get(&self, unit: MassUnit) -> f64 {
+        self.value * self.unit.grams() / unit.grams()
+    }
+}
+
+impl FromStr for Mass {
+    type Err = String;
+
+    /// Parses a number with an optional unit, e.g. `212582`, `212582t`, `6.8e9 oz` or `5000kg`.
+    /// Plain numbers are tonnes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E')
+            .unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value: f64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid mass '{s}', use e.g. 212582t, 6.8e9oz or 5000kg"))?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("invalid mass '{s}', must not be negative"));
+        }
+        let unit = match unit.trim() {
+            "" => MassUnit::Tonne,
+            unit => unit.parse()?,
+        };
+        Ok(Mass::new(value, unit))
+    }
+}
+
+impl fmt::Display for Mass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tonne_in_troy_ounces() {
+        let ounces = Mass::tonnes(1.0).get(MassUnit::TroyOunce);
+        assert!((ounces - 32150.7466).abs() < 1e-4, "{ounces}");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Mass::kilograms(1000.0).get(MassUnit::Tonne), 1.0);
+        assert_eq!(Mass::tonnes(2.0).get(MassUnit::Kilogram), 2000.0);
+        let grams = Mass::troy_ounces(1.0).get(MassUnit::Kilogram) * 1000.0;
+        assert!((grams - 31.1034768).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_mass() {
+        assert_eq!("212582".parse(), Ok(Mass::tonnes(212582.0)));
+        assert_eq!("212582t".parse(), Ok(Mass::tonnes(212582.0)));
+        assert_eq!("6.8e9 oz".parse(), Ok(Mass::troy_ounces(6.8e9)));
+        assert_eq!("5000kg".parse(), Ok(Mass::kilograms(5000.0)));
+        assert!("5000 lb".parse::<Mass>().is_err());
+        assert!("-1t".parse::<Mass>().is_err());
+        assert!("t".parse::<Mass>().is_err());
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let mass = Mass::troy_ounces(1.5e9);
+        assert_eq!(mass.to_string().parse(), Ok(mass));
+    }
+}